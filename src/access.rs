@@ -0,0 +1,185 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use serde::Serialize;
+use crate::filenames::serialize_path;
+
+/// Why a file could not be read or written.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ErrorKind {
+    PermissionDenied,
+    ReadOnlyTarget,
+    /// Opened exclusively or busy in another process
+    Locked,
+    /// Listed during the scan but gone when it was read
+    Vanished,
+    /// A symlinked directory that leads back to one of its parents
+    SymlinkLoop,
+    /// A symlink whose target lies outside the scanned root
+    OutsideRoot,
+    Io,
+    Encoding,
+    Verification,
+}
+
+impl ErrorKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ErrorKind::PermissionDenied => "permission-denied",
+            ErrorKind::ReadOnlyTarget => "read-only-target",
+            ErrorKind::Locked => "locked",
+            ErrorKind::Vanished => "vanished",
+            ErrorKind::SymlinkLoop => "symlink-loop",
+            ErrorKind::OutsideRoot => "outside-root",
+            ErrorKind::Io => "io",
+            ErrorKind::Encoding => "encoding",
+            ErrorKind::Verification => "verification",
+        }
+    }
+
+    pub fn classify(error: &io::Error) -> Self {
+        if is_locked(error) {
+            return ErrorKind::Locked;
+        }
+        match error.kind() {
+            io::ErrorKind::NotFound => ErrorKind::Vanished,
+            io::ErrorKind::PermissionDenied => ErrorKind::PermissionDenied,
+            io::ErrorKind::ReadOnlyFilesystem => ErrorKind::ReadOnlyTarget,
+            _ => ErrorKind::Io,
+        }
+    }
+}
+
+#[cfg(unix)]
+fn is_locked(error: &io::Error) -> bool {
+    // EBUSY, ETXTBSY
+    matches!(error.raw_os_error(), Some(16 | 26))
+}
+
+#[cfg(windows)]
+fn is_locked(error: &io::Error) -> bool {
+    // ERROR_SHARING_VIOLATION, ERROR_LOCK_VIOLATION
+    matches!(error.raw_os_error(), Some(32 | 33))
+}
+
+#[cfg(not(any(unix, windows)))]
+fn is_locked(_error: &io::Error) -> bool {
+    false
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct FileError {
+    pub kind: ErrorKind,
+    pub message: String,
+}
+
+impl FileError {
+    pub fn new(kind: ErrorKind, message: String) -> Self {
+        FileError { kind, message }
+    }
+}
+
+impl From<&io::Error> for FileError {
+    fn from(error: &io::Error) -> Self {
+        FileError::new(ErrorKind::classify(error), error.to_string())
+    }
+}
+
+/// A directory or entry the scan could not descend into.
+#[derive(Debug, Clone, Serialize)]
+pub struct ScanError {
+    #[serde(serialize_with = "serialize_path")]
+    pub path: PathBuf,
+    #[serde(flatten)]
+    pub error: FileError,
+}
+
+/// What to do when a conversion would overwrite a read-only file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReadOnlyPolicy {
+    /// Leave the file alone without counting it as a failure
+    Skip,
+    /// Leave the file alone and report it as failed
+    Report,
+    /// Make the file writable for the conversion and restore its mode afterwards
+    Lift,
+}
+
+impl ReadOnlyPolicy {
+    pub fn from_str(s: &str) -> Option<Self> {
+        match s.to_lowercase().as_str() {
+            "skip" => Some(ReadOnlyPolicy::Skip),
+            "report" => Some(ReadOnlyPolicy::Report),
+            "lift" => Some(ReadOnlyPolicy::Lift),
+            _ => None,
+        }
+    }
+}
+
+pub fn is_read_only(path: &Path) -> bool {
+    fs::metadata(path).is_ok_and(|m| m.permissions().readonly())
+}
+
+/// The given permissions with write access for the owner added.
+pub fn writable(mut permissions: fs::Permissions) -> fs::Permissions {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        permissions.set_mode(permissions.mode() | 0o200);
+    }
+    #[cfg(not(unix))]
+    #[allow(clippy::permissions_set_readonly_false)]
+    permissions.set_readonly(false);
+    permissions
+}
+
+/// Makes a file writable by its owner and returns the permissions to restore.
+pub fn lift_read_only(path: &Path) -> io::Result<fs::Permissions> {
+    let original = fs::metadata(path)?.permissions();
+    fs::set_permissions(path, writable(original.clone()))?;
+    Ok(original)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TempDir;
+
+    #[test]
+    fn classifies_io_errors() {
+        let classify = |error: io::Error| ErrorKind::classify(&error);
+        assert_eq!(classify(io::ErrorKind::NotFound.into()), ErrorKind::Vanished);
+        assert_eq!(classify(io::ErrorKind::PermissionDenied.into()), ErrorKind::PermissionDenied);
+        assert_eq!(classify(io::ErrorKind::ReadOnlyFilesystem.into()), ErrorKind::ReadOnlyTarget);
+        assert_eq!(classify(io::ErrorKind::InvalidData.into()), ErrorKind::Io);
+        #[cfg(unix)]
+        {
+            assert_eq!(classify(io::Error::from_raw_os_error(16)), ErrorKind::Locked);
+            assert_eq!(classify(io::Error::from_raw_os_error(26)), ErrorKind::Locked);
+        }
+    }
+
+    #[test]
+    fn lifts_and_reports_read_only_files() {
+        let dir = TempDir::new();
+        let path = dir.write("a.txt", "text");
+        let mut permissions = fs::metadata(&path).unwrap().permissions();
+        permissions.set_readonly(true);
+        fs::set_permissions(&path, permissions).unwrap();
+        assert!(is_read_only(&path));
+
+        let original = lift_read_only(&path).unwrap();
+        assert!(original.readonly());
+        assert!(!is_read_only(&path));
+        assert!(!is_read_only(&dir.path().join("missing.txt")));
+    }
+
+    #[test]
+    fn parses_read_only_policies() {
+        assert_eq!(ReadOnlyPolicy::from_str("Skip"), Some(ReadOnlyPolicy::Skip));
+        assert_eq!(ReadOnlyPolicy::from_str("report"), Some(ReadOnlyPolicy::Report));
+        assert_eq!(ReadOnlyPolicy::from_str("lift"), Some(ReadOnlyPolicy::Lift));
+        assert_eq!(ReadOnlyPolicy::from_str("force"), None);
+    }
+}
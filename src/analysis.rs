@@ -0,0 +1,345 @@
+use std::path::{Path, PathBuf};
+use encoding_rs::DecoderResult;
+use crate::access::FileError;
+use crate::compression;
+use crate::config::SourceOverrides;
+use crate::detection::{
+    apply_declaration_hint, declaration_matches, detect_anomalies, detect_encoding_with_confidence,
+    detect_line_endings, detect_segments, find_declarations, resolve_label, EncodingDeclaration, FileEncoding,
+};
+use crate::findings::{byte_position, position, Finding, Severity};
+use crate::mojibake::detect_mojibake;
+use crate::normalization::detect_normalization;
+use crate::output::FileReport;
+use crate::properties::{count_escapes, is_properties_file};
+use crate::security;
+
+// Below this, double-encoding patterns are too likely to be legitimate text
+const MOJIBAKE_REPORT_THRESHOLD: f64 = 0.3;
+const MOJIBAKE_WARNING_THRESHOLD: f64 = 0.6;
+
+#[derive(Clone, Copy)]
+pub struct AnalysisOptions<'a> {
+    /// Prefer an in-file encoding declaration over the detected encoding
+    /// when the content decodes cleanly with it
+    pub trust_declarations: bool,
+    /// Look for bidirectional controls, invisible characters and confusables
+    pub security_scan: bool,
+    /// Report UTF-8 file names that are not in NFC
+    pub nfc_filenames: bool,
+    /// Descend into zip and tar archives
+    pub scan_archives: bool,
+    /// Analyze the decompressed content of gzip, xz and zstd files
+    pub decompress: bool,
+    /// Source encodings forced with --from or the config file
+    pub overrides: &'a SourceOverrides,
+    /// Encodings files are expected to be in; anything else is reported as
+    /// a policy violation. Empty for no policy
+    pub expected_encodings: &'a [String],
+    /// Encoding the files will be converted to, if any
+    pub target_encoding: Option<&'a str>,
+}
+
+pub fn analyze_file(path: &Path, name: String, options: &AnalysisOptions) -> FileReport {
+    match compression::read_file(path, options.decompress) {
+        Ok((content, codec)) => {
+            let mut report = analyze_bytes(path.to_path_buf(), name, &content, options);
+            report.compression = codec;
+            report
+        }
+        Err(e) if options.decompress
+            && compression::Codec::from_path(path).is_some()
+            && matches!(compression::sniff(path), Ok(Some(_))) =>
+        {
+            // Fall back to the raw bytes so the file still shows up
+            let mut report = analyze_file(path, name, &AnalysisOptions { decompress: false, ..*options });
+            report.findings.push(Finding::new(
+                "decompression-failed",
+                Severity::Error,
+                format!("Could not decompress: {}", e),
+            ));
+            report
+        }
+        Err(e) => FileReport {
+            root: None,
+            path: path.to_path_buf(),
+            name,
+            encoding: FileEncoding {
+                encoding: "binary/unreadable".to_string(),
+                bom: None,
+            },
+            confidence: 0.0,
+            segments: None,
+            declarations: Vec::new(),
+            normalization: None,
+            line_endings: None,
+            findings: Vec::new(),
+            compression: None,
+            archive: None,
+            error: Some(FileError::from(&e)),
+        },
+    }
+}
+
+pub fn analyze_bytes(
+    path: PathBuf,
+    name: String,
+    content: &[u8],
+    options: &AnalysisOptions,
+) -> FileReport {
+    let (mut encoding, confidence) = detect_encoding_with_confidence(content);
+    let mut findings = Vec::new();
+
+    let declarations = find_declarations(content);
+    if options.trust_declarations {
+        if let Some((declared, kind)) = apply_declaration_hint(content, &encoding, &declarations) {
+            findings.push(Finding::new(
+                "declaration-hint",
+                Severity::Info,
+                format!(
+                    "Using {} from the {} instead of detected {}",
+                    declared.encoding, kind.describe(), encoding.encoding
+                ),
+            ));
+            encoding = declared;
+        }
+    }
+    if let Some(forced) = options.overrides.lookup(&path) {
+        findings.extend(check_override(content, forced, &encoding));
+        encoding = FileEncoding { encoding: forced.to_string(), bom: encoding.bom };
+    }
+    findings.extend(check_declarations(&declarations, &encoding));
+    findings.extend(check_policy(options.expected_encodings, &encoding));
+    findings.extend(detect_anomalies(content, &encoding));
+
+    // Only ASCII-compatible files that failed the UTF-8 check can contain
+    // sections in different encodings worth reporting
+    let segments = if encoding.bom.is_none() && can_be_mixed(&encoding) {
+        Some(detect_segments(content)).filter(|segments| segments.len() > 1)
+    } else {
+        None
+    };
+
+    let mut normalization = None;
+    let mut line_endings = None;
+    if let Some(text) = decode_text(content, &encoding) {
+        normalization = detect_normalization(&text);
+        line_endings = detect_line_endings(&text);
+        findings.extend(check_mojibake(&text));
+        if is_properties_file(&path) {
+            findings.extend(check_properties(&text, &encoding, options.target_encoding));
+        }
+        if options.security_scan {
+            findings.extend(security::scan_text(&text));
+        }
+    }
+
+    FileReport {
+        root: None,
+        path,
+        name,
+        encoding,
+        confidence,
+        segments,
+        declarations,
+        normalization,
+        line_endings,
+        findings,
+        compression: None,
+        archive: None,
+        error: None,
+    }
+}
+
+fn check_properties(text: &str, encoding: &FileEncoding, target: Option<&str>) -> Vec<Finding> {
+    let mut findings = Vec::new();
+    let escapes = count_escapes(text);
+    if escapes.total > 0 {
+        findings.push(Finding::new(
+            "properties-escapes",
+            Severity::Info,
+            format!(
+                "{} \\uXXXX escape(s), {} outside Latin-1; a plain re-encode keeps them \
+                 literally, use --properties to translate them",
+                escapes.total, escapes.non_latin1
+            ),
+        ));
+    }
+
+    // Characters that pre-Java 9 readers can only see through escapes, which
+    // only matters when the file is about to be saved for such a reader
+    let is_latin1 = matches!(encoding.encoding.as_str(), "ISO-8859-1" | "windows-1252" | "ASCII");
+    let to_latin1 = target.is_some_and(|to| to.eq_ignore_ascii_case("ISO-8859-1"));
+    if let Some(offset) = text.find(|c: char| c as u32 > 0xFF).filter(|_| to_latin1 && !is_latin1) {
+        findings.push(Finding::new(
+            "properties-unmappable",
+            Severity::Warning,
+            "Characters outside Latin-1 would be lost when saving as ISO-8859-1 without escaping"
+                .to_string(),
+        ).at(position(text, offset)));
+    }
+    findings
+}
+
+/// Reports where a forced source encoding disagrees with detection and
+/// whether the content actually decodes with it.
+fn check_override(content: &[u8], forced: &str, detected: &FileEncoding) -> Vec<Finding> {
+    let mut findings = Vec::new();
+    if declaration_matches(forced, detected) == Some(false) {
+        findings.push(Finding::new(
+            "override-mismatch",
+            Severity::Warning,
+            format!("Source encoding forced to {} but detected {}", forced, detected.encoding),
+        ));
+    }
+
+    let Some(encoding) = resolve_label(forced) else { return findings };
+    let mut decoder = encoding.new_decoder();
+    let Some(capacity) = decoder.max_utf8_buffer_length_without_replacement(content.len()) else {
+        return findings;
+    };
+    let mut text = String::with_capacity(capacity);
+    let (result, read) = decoder.decode_to_string_without_replacement(content, &mut text, true);
+    if let DecoderResult::Malformed(bad, after) = result {
+        let offset = read - bad as usize - after as usize;
+        findings.push(Finding::new(
+            "override-decode-failed",
+            Severity::Error,
+            format!("Content does not decode as {}; conversion will fail", forced),
+        ).at(byte_position(content, offset)));
+    }
+    findings
+}
+
+/// Reports content that is in none of the expected encodings. Pure ASCII
+/// satisfies any ASCII-compatible expectation, and files with no text to
+/// judge are left to the other checks. Content in an encoding that cannot
+/// be compared with the expected ones is a violation too.
+fn check_policy(expected: &[String], encoding: &FileEncoding) -> Option<Finding> {
+    if expected.is_empty() || matches!(encoding.encoding.as_str(), "empty file" | "binary/unreadable") {
+        return None;
+    }
+    if expected.iter().any(|name| declaration_matches(name, encoding) == Some(true)) {
+        return None;
+    }
+    Some(Finding::new(
+        "encoding-policy",
+        Severity::Error,
+        format!("Content is {}, expected {}", encoding.encoding, expected.join(" or ")),
+    ))
+}
+
+fn check_declarations(declarations: &[EncodingDeclaration], encoding: &FileEncoding) -> Vec<Finding> {
+    declarations.iter()
+        .filter_map(|d| {
+            let finding = match declaration_matches(&d.encoding, encoding) {
+                Some(true) => return None,
+                Some(false) => Finding::new(
+                    "declaration-mismatch",
+                    Severity::Warning,
+                    format!(
+                        "{} declares {} but the content is {}",
+                        d.kind.describe(), d.encoding, encoding.encoding
+                    ),
+                ),
+                None if encoding.encoding == "empty file" => return None,
+                None => Finding::new(
+                    "unknown-declaration",
+                    Severity::Info,
+                    format!("{} declares unrecognised encoding {}", d.kind.describe(), d.encoding),
+                ),
+            };
+            Some(finding.at((d.line, d.column)))
+        })
+        .collect()
+}
+
+/// Decodes content with its detected encoding for the text-level checks.
+/// Returns None if the encoding is unknown or the content does not decode cleanly.
+fn decode_text(content: &[u8], encoding: &FileEncoding) -> Option<String> {
+    let label = match encoding.encoding.as_str() {
+        "ASCII" => "UTF-8",
+        "empty file" | "binary/unreadable" => return None,
+        other => other,
+    };
+    let decoder = encoding_rs::Encoding::for_label(label.as_bytes())?;
+    let (text, _, had_errors) = decoder.decode(content);
+    if had_errors {
+        return None;
+    }
+    Some(text.into_owned())
+}
+
+fn check_mojibake(text: &str) -> Option<Finding> {
+    let report = detect_mojibake(text)?;
+    if report.confidence < MOJIBAKE_REPORT_THRESHOLD {
+        return None;
+    }
+    let severity = if report.confidence >= MOJIBAKE_WARNING_THRESHOLD {
+        Severity::Warning
+    } else {
+        Severity::Info
+    };
+    Some(Finding::new(
+        "mojibake",
+        severity,
+        format!(
+            "{} sequence(s) look like UTF-8 decoded as Windows-1252 ({} layer(s))",
+            report.suspicious, report.layers
+        ),
+    )
+    .at(position(text, report.first_offset))
+    .with_confidence(report.confidence))
+}
+
+fn can_be_mixed(encoding: &FileEncoding) -> bool {
+    let name = encoding.encoding.to_uppercase();
+    !matches!(name.as_str(), "ASCII" | "UTF-8" | "EMPTY FILE" | "BINARY/UNREADABLE")
+        && !name.starts_with("UTF-16")
+        && !name.starts_with("UTF-32")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn encoding(name: &str) -> FileEncoding {
+        FileEncoding { encoding: name.to_string(), bom: None }
+    }
+
+    fn utf8() -> FileEncoding {
+        encoding("UTF-8")
+    }
+
+    fn violates(expected: &[&str], content: &str) -> bool {
+        let expected: Vec<String> = expected.iter().map(|name| name.to_string()).collect();
+        check_policy(&expected, &encoding(content)).is_some()
+    }
+
+    #[test]
+    fn policy_accepts_expected_and_ascii_content() {
+        assert!(!violates(&[], "windows-1252"));
+        assert!(!violates(&["UTF-8"], "UTF-8"));
+        assert!(!violates(&["UTF-8", "windows-1252"], "windows-1252"));
+        assert!(!violates(&["UTF-8"], "ASCII"));
+        assert!(!violates(&["UTF-8"], "empty file"));
+        assert!(!violates(&["UTF-8"], "binary/unreadable"));
+    }
+
+    #[test]
+    fn policy_reports_other_and_unresolvable_encodings() {
+        assert!(violates(&["UTF-8"], "windows-1252"));
+        assert!(violates(&["UTF-16LE"], "ASCII"));
+        // chardet can name encodings the converter does not know
+        assert!(violates(&["UTF-8"], "EUC-TW"));
+    }
+
+    #[test]
+    fn warns_about_unmappable_properties_only_for_latin1_target() {
+        let text = "greeting=\u{4f60}\u{597d}\n";
+        let kinds = |target| check_properties(text, &utf8(), target).iter().map(|f| f.kind).collect::<Vec<_>>();
+        assert_eq!(kinds(Some("ISO-8859-1")), vec!["properties-unmappable"]);
+        assert!(kinds(Some("UTF-8")).is_empty());
+        assert!(kinds(None).is_empty());
+    }
+}
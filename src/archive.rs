@@ -0,0 +1,315 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::ffi::OsString;
+use std::fs::File;
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use log::warn;
+use zip::write::SimpleFileOptions;
+use crate::analysis::{analyze_bytes, AnalysisOptions};
+use crate::compression;
+use crate::conversion::{ConversionError, ConversionOptions, EncodingConverter};
+use crate::detection::FileEncoding;
+use crate::filter::FileFilter;
+use crate::statistics::Statistics;
+
+#[derive(Debug, Clone, Copy)]
+pub enum ArchiveKind {
+    Zip,
+    Tar,
+    TarGz,
+}
+
+impl ArchiveKind {
+    pub fn from_path(path: &Path) -> Option<Self> {
+        let name = path.file_name()?.to_str()?.to_lowercase();
+        if name.ends_with(".zip") {
+            Some(ArchiveKind::Zip)
+        } else if name.ends_with(".tar.gz") || name.ends_with(".tgz") {
+            Some(ArchiveKind::TarGz)
+        } else if name.ends_with(".tar") {
+            Some(ArchiveKind::Tar)
+        } else {
+            None
+        }
+    }
+}
+
+/// Where a scanned file lives when it was read from inside an archive.
+#[derive(Debug, Clone)]
+pub struct ArchiveEntry {
+    pub archive: PathBuf,
+    pub entry: String,
+}
+
+/// Reports entries as `archive.zip!/path/in/archive`.
+fn entry_path(archive: &Path, entry: &str) -> PathBuf {
+    let mut path = OsString::from(archive.as_os_str());
+    path.push("!/");
+    path.push(entry);
+    PathBuf::from(path)
+}
+
+pub fn scan_archive(
+    path: &Path,
+    kind: ArchiveKind,
+    stats: &mut Statistics,
+    filter: &FileFilter,
+    options: &AnalysisOptions,
+) -> Result<(), Box<dyn Error>> {
+    for_each_entry(path, kind, |entry, content| {
+        let entry_name = Path::new(entry);
+        if !filter.should_include(entry_name) {
+            return;
+        }
+        let content = match content {
+            Ok(content) => content,
+            Err(e) => {
+                stats.add_error(&entry_path(path, entry), &e);
+                return;
+            }
+        };
+        let name = entry_name.file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_else(|| entry.to_string());
+        let mut report = analyze_bytes(entry_path(path, entry), name, &content, options);
+        report.archive = Some(ArchiveEntry {
+            archive: path.to_path_buf(),
+            entry: entry.to_string(),
+        });
+        stats.add_file(report);
+    })
+}
+
+/// Calls `visit` with the content of every file in an archive, or the
+/// error reading it, such as content beyond the decompression limit.
+fn for_each_entry(
+    path: &Path,
+    kind: ArchiveKind,
+    mut visit: impl FnMut(&str, io::Result<Vec<u8>>),
+) -> Result<(), Box<dyn Error>> {
+    match kind {
+        ArchiveKind::Zip => {
+            let mut archive = zip::ZipArchive::new(File::open(path)?)?;
+            for index in 0..archive.len() {
+                let mut file = archive.by_index(index)?;
+                if file.is_dir() {
+                    continue;
+                }
+                let content = compression::read_decompressed(&mut file);
+                visit(file.name(), content);
+            }
+        }
+        ArchiveKind::Tar => read_tar(File::open(path)?, visit)?,
+        ArchiveKind::TarGz => read_tar(GzDecoder::new(File::open(path)?), visit)?,
+    }
+    Ok(())
+}
+
+fn read_tar<R: Read>(reader: R, mut visit: impl FnMut(&str, io::Result<Vec<u8>>)) -> Result<(), Box<dyn Error>> {
+    let mut archive = tar::Archive::new(reader);
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        if !entry.header().entry_type().is_file() {
+            continue;
+        }
+        let name = entry.path()?.to_string_lossy().into_owned();
+        let content = compression::read_decompressed(&mut entry);
+        visit(&name, content);
+    }
+    Ok(())
+}
+
+/// Writes a copy of an archive with the given entries re-encoded. Entries
+/// that are not listed or fail to convert are copied unchanged.
+/// Returns the listed entries that failed, with their errors.
+pub fn convert_archive(
+    path: &Path,
+    kind: ArchiveKind,
+    output_path: &Path,
+    encodings: &HashMap<String, FileEncoding>,
+    to: &str,
+    options: &ConversionOptions,
+) -> Result<HashMap<String, ConversionError>, Box<dyn Error>> {
+    let mut failed = HashMap::new();
+    let mut convert_entry = |entry: &str, content: &[u8]| -> Option<Vec<u8>> {
+        let encoding = encodings.get(entry)?;
+        match EncodingConverter::convert(content, encoding, to, options) {
+            Ok(output) => Some(output),
+            Err(e) => {
+                warn!("Keeping {}!/{} unchanged: {}", path.display(), entry, e);
+                failed.insert(entry.to_string(), e);
+                None
+            }
+        }
+    };
+
+    let converts = |entry: &str| encodings.contains_key(entry);
+
+    match kind {
+        ArchiveKind::Zip => {
+            let mut archive = zip::ZipArchive::new(File::open(path)?)?;
+            let mut writer = zip::ZipWriter::new(File::create(output_path)?);
+            for index in 0..archive.len() {
+                let mut file = archive.by_index(index)?;
+                // Entries this build cannot compress the same way are rewritten with Deflate
+                let method = if zip::SUPPORTED_COMPRESSION_METHODS.contains(&file.compression()) {
+                    file.compression()
+                } else {
+                    zip::CompressionMethod::Deflated
+                };
+                let mut entry_options = SimpleFileOptions::default().compression_method(method);
+                if let Some(modified) = file.last_modified() {
+                    entry_options = entry_options.last_modified_time(modified);
+                }
+                if let Some(mode) = file.unix_mode() {
+                    entry_options = entry_options.unix_permissions(mode);
+                }
+
+                if file.is_dir() {
+                    writer.add_directory(file.name(), entry_options)?;
+                    continue;
+                }
+                if !encodings.contains_key(file.name()) {
+                    writer.raw_copy_file(file)?;
+                    continue;
+                }
+
+                let content = compression::read_decompressed(&mut file)?;
+                let name = file.name().to_string();
+                let output = convert_entry(&name, &content).unwrap_or(content);
+                writer.start_file(name, entry_options)?;
+                writer.write_all(&output)?;
+            }
+            writer.finish()?;
+        }
+        ArchiveKind::Tar => {
+            let builder = tar::Builder::new(File::create(output_path)?);
+            rewrite_tar(File::open(path)?, builder, &converts, &mut convert_entry)?.into_inner()?;
+        }
+        ArchiveKind::TarGz => {
+            let encoder = GzEncoder::new(File::create(output_path)?, flate2::Compression::default());
+            let builder = tar::Builder::new(encoder);
+            rewrite_tar(GzDecoder::new(File::open(path)?), builder, &converts, &mut convert_entry)?
+                .into_inner()?
+                .finish()?;
+        }
+    }
+
+    Ok(failed)
+}
+
+/// Copies a tar stream into `builder`, passing the files `converts` accepts
+/// through `convert_entry`. Everything else is streamed unchanged, so only
+/// converted entries are held in memory.
+fn rewrite_tar<R: Read, W: Write>(
+    reader: R,
+    mut builder: tar::Builder<W>,
+    converts: &dyn Fn(&str) -> bool,
+    convert_entry: &mut impl FnMut(&str, &[u8]) -> Option<Vec<u8>>,
+) -> Result<tar::Builder<W>, Box<dyn Error>> {
+    let mut archive = tar::Archive::new(reader);
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        let mut header = entry.header().clone();
+        let name = entry.path()?.into_owned();
+        // PAX records carry long names, precise mtimes and the like; only the
+        // size changes with the content
+        let mut pax = Vec::new();
+        if let Some(extensions) = entry.pax_extensions()? {
+            for extension in extensions {
+                let extension = extension?;
+                let key = extension.key()?;
+                if key != "size" {
+                    pax.push((key.to_string(), extension.value_bytes().to_vec()));
+                }
+            }
+        }
+        if !pax.is_empty() {
+            builder.append_pax_extensions(pax.iter().map(|(key, value)| (key.as_str(), value.as_slice())))?;
+        }
+
+        let entry_name = name.to_string_lossy();
+        // append_data writes GNU long-name records for paths over 100 bytes
+        if header.entry_type().is_file() && converts(&entry_name) {
+            let content = compression::read_decompressed(&mut entry)?;
+            let output = convert_entry(&entry_name, &content).unwrap_or(content);
+            header.set_size(output.len() as u64);
+            builder.append_data(&mut header, &name, output.as_slice())?;
+        } else {
+            builder.append_data(&mut header, &name, &mut entry)?;
+        }
+    }
+    Ok(builder)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rewrite_tar_keeps_pax_headers() {
+        let mut builder = tar::Builder::new(Vec::new());
+        builder.append_pax_extensions([("mtime", b"1700000000.5".as_slice()), ("comment", b"kept".as_slice())]).unwrap();
+        let mut header = tar::Header::new_ustar();
+        header.set_size(5);
+        header.set_mode(0o644);
+        header.set_cksum();
+        builder.append_data(&mut header, "a.txt", b"hello".as_slice()).unwrap();
+        let original = builder.into_inner().unwrap();
+
+        let mut convert = |_: &str, _: &[u8]| Some(b"hello, world".to_vec());
+        let rewritten = rewrite_tar(original.as_slice(), tar::Builder::new(Vec::new()), &|_| true, &mut convert)
+            .unwrap()
+            .into_inner()
+            .unwrap();
+
+        let mut archive = tar::Archive::new(rewritten.as_slice());
+        let mut entry = archive.entries().unwrap().next().unwrap().unwrap();
+        let pax: Vec<(String, Vec<u8>)> = entry.pax_extensions().unwrap().unwrap()
+            .map(|e| e.unwrap())
+            .map(|e| (e.key().unwrap().to_string(), e.value_bytes().to_vec()))
+            .collect();
+        assert!(pax.contains(&("mtime".to_string(), b"1700000000.5".to_vec())));
+        assert!(pax.contains(&("comment".to_string(), b"kept".to_vec())));
+        let mut content = String::new();
+        entry.read_to_string(&mut content).unwrap();
+        assert_eq!(content, "hello, world");
+    }
+
+    #[test]
+    fn rewrite_tar_only_converts_listed_files() {
+        let mut builder = tar::Builder::new(Vec::new());
+        for (name, content) in [("a.txt", b"old a".as_slice()), ("b.bin", b"\x00\x01 kept".as_slice())] {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(content.len() as u64);
+            header.set_mode(0o644);
+            header.set_cksum();
+            builder.append_data(&mut header, name, content).unwrap();
+        }
+        let original = builder.into_inner().unwrap();
+
+        let mut convert = |_: &str, _: &[u8]| Some(b"new a, longer".to_vec());
+        let rewritten = rewrite_tar(original.as_slice(), tar::Builder::new(Vec::new()), &|name| name == "a.txt", &mut convert)
+            .unwrap()
+            .into_inner()
+            .unwrap();
+
+        let mut archive = tar::Archive::new(rewritten.as_slice());
+        let entries: Vec<(String, Vec<u8>)> = archive.entries().unwrap()
+            .map(|entry| {
+                let mut entry = entry.unwrap();
+                let name = entry.path().unwrap().to_string_lossy().into_owned();
+                let mut content = Vec::new();
+                entry.read_to_end(&mut content).unwrap();
+                (name, content)
+            })
+            .collect();
+        assert_eq!(entries, [
+            ("a.txt".to_string(), b"new a, longer".to_vec()),
+            ("b.bin".to_string(), b"\x00\x01 kept".to_vec()),
+        ]);
+    }
+}
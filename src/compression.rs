@@ -0,0 +1,185 @@
+use std::fs::File;
+use std::io::{self, BufReader, Read, Write};
+use std::path::Path;
+use flate2::read::MultiGzDecoder;
+use flate2::write::GzEncoder;
+use serde::Serialize;
+use xz2::read::XzDecoder;
+use xz2::write::XzEncoder;
+
+/// Compression formats that rotated logs and dumps commonly use.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Codec {
+    Gzip,
+    Xz,
+    Zstd,
+}
+
+// xz's default preset, a good balance for text
+const XZ_LEVEL: u32 = 6;
+
+// Text files are never this large; anything bigger is a decompression bomb
+const MAX_DECOMPRESSED_SIZE: u64 = 1 << 30;
+
+impl Codec {
+    pub fn detect(magic: &[u8]) -> Option<Self> {
+        match magic {
+            [0x1F, 0x8B, ..] => Some(Codec::Gzip),
+            [0xFD, b'7', b'z', b'X', b'Z', 0x00, ..] => Some(Codec::Xz),
+            [0x28, 0xB5, 0x2F, 0xFD, ..] => Some(Codec::Zstd),
+            _ => None,
+        }
+    }
+
+    /// Codec implied by a compressed file's extension. Compressed tarballs
+    /// are archives rather than compressed text, so they have none.
+    pub fn from_path(path: &Path) -> Option<Self> {
+        let name = path.file_name()?.to_str()?.to_lowercase();
+        let (stem, extension) = name.rsplit_once('.')?;
+        if stem.ends_with(".tar") {
+            return None;
+        }
+        match extension {
+            "gz" => Some(Codec::Gzip),
+            "xz" => Some(Codec::Xz),
+            "zst" => Some(Codec::Zstd),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Codec::Gzip => "gzip",
+            Codec::Xz => "xz",
+            Codec::Zstd => "zstd",
+        }
+    }
+
+    /// Wraps a reader so it yields the decompressed stream. Concatenated
+    /// gzip members and xz streams are read as one, like `zcat` does.
+    pub fn decoder<'a, R: Read + 'a>(self, reader: R) -> io::Result<Box<dyn Read + 'a>> {
+        Ok(match self {
+            Codec::Gzip => Box::new(MultiGzDecoder::new(reader)),
+            Codec::Xz => Box::new(XzDecoder::new_multi_decoder(reader)),
+            Codec::Zstd => Box::new(zstd::Decoder::new(reader)?),
+        })
+    }
+
+    pub fn compress(self, data: &[u8]) -> io::Result<Vec<u8>> {
+        match self {
+            Codec::Gzip => {
+                let mut encoder = GzEncoder::new(Vec::new(), flate2::Compression::default());
+                encoder.write_all(data)?;
+                encoder.finish()
+            }
+            Codec::Xz => {
+                let mut encoder = XzEncoder::new(Vec::new(), XZ_LEVEL);
+                encoder.write_all(data)?;
+                encoder.finish()
+            }
+            Codec::Zstd => zstd::encode_all(data, zstd::DEFAULT_COMPRESSION_LEVEL),
+        }
+    }
+}
+
+/// Reads a file, transparently decompressing it when `decompress` is set
+/// and both its extension and its magic number name the same codec.
+/// Returns the codec that was used. Decompressed content larger than
+/// 1 GiB is an error.
+pub fn read_file(path: &Path, decompress: bool) -> io::Result<(Vec<u8>, Option<Codec>)> {
+    read_file_limited(path, decompress, MAX_DECOMPRESSED_SIZE)
+}
+
+fn read_file_limited(path: &Path, decompress: bool, limit: u64) -> io::Result<(Vec<u8>, Option<Codec>)> {
+    let mut file = File::open(path)?;
+    let mut magic = [0u8; 6];
+    let read = read_up_to(&mut file, &mut magic)?;
+    let codec = Codec::detect(&magic[..read])
+        .filter(|&codec| decompress && Codec::from_path(path) == Some(codec));
+
+    let mut reader = BufReader::new((&magic[..read]).chain(file));
+    let mut content = Vec::new();
+    match codec {
+        Some(codec) => content = read_capped(codec.decoder(reader)?, limit)?,
+        None => {
+            reader.read_to_end(&mut content)?;
+        }
+    }
+    Ok((content, codec))
+}
+
+/// Reads decompressed data, such as an archive entry, up to the same
+/// limit as compressed files.
+pub fn read_decompressed(reader: impl Read) -> io::Result<Vec<u8>> {
+    read_capped(reader, MAX_DECOMPRESSED_SIZE)
+}
+
+fn read_capped(reader: impl Read, limit: u64) -> io::Result<Vec<u8>> {
+    let mut content = Vec::new();
+    reader.take(limit + 1).read_to_end(&mut content)?;
+    if content.len() as u64 > limit {
+        return Err(io::Error::other(format!("decompressed content exceeds {} bytes", limit)));
+    }
+    Ok(content)
+}
+
+/// Identifies the codec of a file from its magic number.
+pub fn sniff(path: &Path) -> io::Result<Option<Codec>> {
+    let mut magic = [0u8; 6];
+    let read = read_up_to(&mut File::open(path)?, &mut magic)?;
+    Ok(Codec::detect(&magic[..read]))
+}
+
+fn read_up_to(file: &mut File, buf: &mut [u8]) -> io::Result<usize> {
+    let mut total = 0;
+    while total < buf.len() {
+        match file.read(&mut buf[total..])? {
+            0 => break,
+            n => total += n,
+        }
+    }
+    Ok(total)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TempDir;
+
+    #[test]
+    fn decompresses_each_codec_by_extension() {
+        let dir = TempDir::new();
+        let text = "Grüße aus Köln\n".as_bytes();
+        for (codec, name) in [(Codec::Gzip, "app.log.gz"), (Codec::Xz, "dump.sql.xz"), (Codec::Zstd, "notes.txt.zst")] {
+            let path = dir.write(name, codec.compress(text).unwrap());
+            assert_eq!(read_file(&path, true).unwrap(), (text.to_vec(), Some(codec)));
+        }
+    }
+
+    #[test]
+    fn leaves_compressed_content_alone_when_disabled() {
+        let dir = TempDir::new();
+        let compressed = Codec::Gzip.compress(b"plain text").unwrap();
+        let path = dir.write("disabled.log.gz", &compressed);
+        assert_eq!(read_file(&path, false).unwrap(), (compressed, None));
+    }
+
+    #[test]
+    fn only_decompresses_matching_extensions() {
+        let dir = TempDir::new();
+        let compressed = Codec::Gzip.compress(b"not a tar").unwrap();
+        for name in ["bundle.tgz", "bundle.tar.gz", "misnamed.txt", "wrong.xz"] {
+            let path = dir.write(name, &compressed);
+            assert_eq!(read_file(&path, true).unwrap(), (compressed.clone(), None), "{}", name);
+        }
+    }
+
+    #[test]
+    fn rejects_oversized_decompressed_content() {
+        let dir = TempDir::new();
+        let path = dir.write("bomb.log.gz", Codec::Gzip.compress(&[b'a'; 4096]).unwrap());
+        assert!(read_file_limited(&path, true, 1024).is_err());
+        assert_eq!(read_file_limited(&path, true, 4096).unwrap().0.len(), 4096);
+    }
+}
@@ -0,0 +1,103 @@
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use serde::Deserialize;
+use crate::conversion::EncodingConverter;
+use crate::detection::declared_encoding_name;
+
+/// Settings read from the JSON file given with --config.
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    /// Source encodings to use instead of the detected ones
+    #[serde(default)]
+    pub overrides: Vec<SourceOverride>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SourceOverride {
+    pub glob: String,
+    pub encoding: String,
+}
+
+impl Config {
+    pub fn load(path: &Path) -> Result<Self, Box<dyn Error>> {
+        let content = fs::read_to_string(path)?;
+        serde_json::from_str(&content)
+            .map_err(|e| format!("Invalid config file '{}': {}", path.display(), e).into())
+    }
+}
+
+/// Resolves the forced source encoding for a path, if any. `--from` applies
+/// to every file; otherwise the last matching glob from the config wins.
+pub struct SourceOverrides {
+    roots: Vec<PathBuf>,
+    from: Option<String>,
+    globs: GlobSet,
+    encodings: Vec<String>,
+}
+
+impl SourceOverrides {
+    pub fn new(roots: &[PathBuf], from: Option<String>, config: &Config) -> Result<Self, Box<dyn Error>> {
+        let mut builder = GlobSetBuilder::new();
+        let mut encodings = Vec::new();
+        for entry in &config.overrides {
+            builder.add(Glob::new(&entry.glob)
+                .map_err(|e| format!("Invalid glob '{}': {}", entry.glob, e))?);
+            encodings.push(source_encoding_name(&entry.encoding)?);
+        }
+        Ok(SourceOverrides {
+            roots: roots.to_vec(),
+            from,
+            globs: builder.build()?,
+            encodings,
+        })
+    }
+
+    /// Globs are matched against the path relative to the scanned root it
+    /// was found under.
+    pub fn lookup(&self, path: &Path) -> Option<&str> {
+        if let Some(from) = &self.from {
+            return Some(from);
+        }
+        let relative = self.roots.iter()
+            .find_map(|root| path.strip_prefix(root).ok())
+            .unwrap_or(path);
+        self.globs.matches(relative).into_iter().max()
+            .map(|index| self.encodings[index].as_str())
+    }
+}
+
+/// Validates a user-supplied encoding label and returns the converter's name for it.
+/// Labels the converter cannot decode are rejected here rather than per file.
+pub fn source_encoding_name(label: &str) -> Result<String, Box<dyn Error>> {
+    let name = declared_encoding_name(label)
+        .ok_or_else(|| format!("Invalid source encoding: '{}'", label))?;
+    if !EncodingConverter::supports(&name) {
+        return Err(format!(
+            "Unsupported source encoding: '{}'. Supported encodings are: UTF-8, UTF-16LE, UTF-16BE, windows-1252, ISO-8859-1",
+            label
+        ).into());
+    }
+    Ok(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn accepts_only_encodings_the_converter_supports() {
+        assert_eq!(source_encoding_name("latin1").unwrap(), "ISO-8859-1");
+        assert_eq!(source_encoding_name("cp1252").unwrap(), "windows-1252");
+        assert!(source_encoding_name("iso-8859-2").is_err());
+        assert!(source_encoding_name("no-such-encoding").is_err());
+
+        let config = Config {
+            overrides: vec![SourceOverride { glob: "*.txt".to_string(), encoding: "shift_jis".to_string() }],
+        };
+        assert!(SourceOverrides::new(&[], None, &config).is_err());
+    }
+}
@@ -1,13 +1,25 @@
-use std::io::{self, Read, Write};
+use std::io::{self, Write};
 use std::fmt;
+use std::path::PathBuf;
 use encoding_rs::*;
-use crate::detection::FileEncoding;
+use log::info;
+use serde::Serialize;
+use crate::access::{ErrorKind, FileError};
+use crate::compression;
+use crate::detection::{detect_segments, find_declarations, resolve_label, EncodingSegment, FileEncoding};
+use crate::filenames::serialize_path;
+use crate::mojibake::repair_mojibake;
+use crate::normalization::NormalizationForm;
+use crate::properties;
 
 #[derive(Debug)]
 pub enum ConversionError {
     IoError(io::Error),
+    DecodeError(String),
     EncodingError(String),
     UnsupportedEncoding(String),
+    /// A mojibake repair produced implausible text and was not applied
+    RepairRejected(String),
 }
 
 impl std::error::Error for ConversionError {}
@@ -16,8 +28,10 @@ impl fmt::Display for ConversionError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
             ConversionError::IoError(e) => write!(f, "IO error: {}", e),
+            ConversionError::DecodeError(msg) => write!(f, "Decode error: {}", msg),
             ConversionError::EncodingError(msg) => write!(f, "Encoding error: {}", msg),
             ConversionError::UnsupportedEncoding(enc) => write!(f, "Unsupported encoding: {}", enc),
+            ConversionError::RepairRejected(msg) => write!(f, "Mojibake repair rejected: {}", msg),
         }
     }
 }
@@ -40,6 +54,29 @@ impl LineEnding {
     }
 }
 
+#[derive(Debug, Clone, Copy)]
+pub struct ConversionOptions {
+    pub line_ending: LineEnding,
+    /// Decode each section of a mixed-encoding file with its own encoding
+    pub repair_mixed: bool,
+    /// Maximum number of double-encoding layers to reverse (0 disables repair)
+    pub repair_mojibake: usize,
+    /// Detection confidence below which double encoding is left alone
+    pub mojibake_confidence: f64,
+    /// Update in-file charset declarations to name the target encoding
+    pub rewrite_declarations: bool,
+    /// Treat the input as a Java `.properties` file and translate `\uXXXX`
+    /// escapes to match the target encoding
+    pub properties: bool,
+    /// Unicode normalization form to apply to the decoded text
+    pub normalize: Option<NormalizationForm>,
+    /// Remove byte order marks left inside the content by concatenation
+    pub strip_embedded_boms: bool,
+    /// Convert the content of gzip/xz/zstd files and recompress it with
+    /// the same codec
+    pub decompress: bool,
+}
+
 pub struct EncodingConverter;
 
 impl EncodingConverter {
@@ -47,27 +84,66 @@ impl EncodingConverter {
         input: &[u8], 
         from: &FileEncoding, 
         to: &str,
-        line_ending: LineEnding,
+        options: &ConversionOptions,
     ) -> Result<Vec<u8>, ConversionError> {
-        let (decoder, encoder) = Self::get_codecs(from, to)?;
-        
+        let text = Self::transform(input, from, to, options)?;
+        Self::encode(&text, to)
+    }
+
+    /// Decodes the input and applies every text-level step of the conversion,
+    /// returning the text that would be encoded.
+    pub fn transform(
+        input: &[u8],
+        from: &FileEncoding,
+        to: &str,
+        options: &ConversionOptions,
+    ) -> Result<String, ConversionError> {
+        Self::get_encoding(to)?;
+
         // Decode from source encoding to UTF-8
-        let (cow, _, had_errors) = decoder.decode(input);
-        if had_errors {
-            return Err(ConversionError::EncodingError(
-                format!("Failed to decode from {}", from.encoding)
-            ));
+        let mut text = Self::decode(input, from, options)?;
+
+        // Decoding already consumed a leading BOM, so any left are embedded
+        if options.strip_embedded_boms && text.contains('\u{FEFF}') {
+            let count = text.matches('\u{FEFF}').count();
+            text = text.replace('\u{FEFF}', "");
+            info!("Stripped {} embedded byte order mark(s)", count);
+        }
+
+        if options.repair_mojibake > 0 {
+            let (repaired, layers) = repair_mojibake(&text, options.repair_mojibake, options.mojibake_confidence)
+                .map_err(ConversionError::RepairRejected)?;
+            if layers > 0 {
+                info!("Reversed {} layer(s) of double encoding", layers);
+                text = repaired;
+            }
+        }
+
+        if options.properties {
+            text = Self::convert_properties_escapes(&text, to);
+        }
+
+        if let Some(form) = options.normalize {
+            text = form.apply(&text);
+        }
+
+        if options.rewrite_declarations {
+            text = Self::rewrite_declarations(&text, to);
         }
 
         // Convert line endings if needed
-        let content = match line_ending {
-            LineEnding::Keep => cow.into_owned(),
-            LineEnding::Unix => Self::convert_to_unix_endings(&cow),
-            LineEnding::Windows => Self::convert_to_windows_endings(&cow),
-        };
+        Ok(match options.line_ending {
+            LineEnding::Keep => text,
+            LineEnding::Unix => Self::convert_to_unix_endings(&text),
+            LineEnding::Windows => Self::convert_to_windows_endings(&text),
+        })
+    }
+
+    pub fn encode(content: &str, to: &str) -> Result<Vec<u8>, ConversionError> {
+        let encoder = Self::get_encoding(to)?;
 
         // Encode to target encoding
-        let (output, _, had_errors) = encoder.encode(&content);
+        let (output, _, had_errors) = encoder.encode(content);
         if had_errors {
             return Err(ConversionError::EncodingError(
                 format!("Failed to encode to {}", to)
@@ -84,6 +160,130 @@ impl EncodingConverter {
         Ok(result)
     }
 
+    /// Byte offsets of the characters the target encoding cannot represent.
+    pub fn unmappable(text: &str, to: &str) -> Result<Vec<usize>, ConversionError> {
+        let encoder = Self::get_encoding(to)?;
+        if encoder == UTF_8 {
+            return Ok(Vec::new());
+        }
+        let mut buf = [0; 4];
+        Ok(text.char_indices()
+            .filter(|(_, c)| !c.is_ascii() && encoder.encode(c.encode_utf8(&mut buf)).2)
+            .map(|(offset, _)| offset)
+            .collect())
+    }
+
+    pub fn decode(
+        input: &[u8],
+        from: &FileEncoding,
+        options: &ConversionOptions,
+    ) -> Result<String, ConversionError> {
+        if options.repair_mixed && from.bom.is_none() {
+            let segments = detect_segments(input);
+            if segments.len() > 1 {
+                return Self::decode_segments(input, &segments);
+            }
+        }
+
+        let decoder = Self::get_encoding(&from.encoding)?;
+        let (cow, _, had_errors) = decoder.decode(input);
+        if had_errors {
+            return Err(ConversionError::DecodeError(
+                format!("Failed to decode from {}", from.encoding)
+            ));
+        }
+        Ok(cow.into_owned())
+    }
+
+    fn decode_segments(
+        input: &[u8],
+        segments: &[EncodingSegment],
+    ) -> Result<String, ConversionError> {
+        let mut text = String::with_capacity(input.len());
+        for segment in segments {
+            let bytes = &input[segment.start_byte..segment.end_byte];
+            let decoder = Self::get_encoding(&segment.encoding)?;
+            let (cow, had_errors) = decoder.decode_without_bom_handling(bytes);
+            if had_errors {
+                return Err(ConversionError::DecodeError(format!(
+                    "Failed to decode lines {}-{} from {}",
+                    segment.start_line, segment.end_line, segment.encoding
+                )));
+            }
+            info!("Decoded lines {}-{} as {}", segment.start_line, segment.end_line, segment.encoding);
+            text.push_str(&cow);
+        }
+        Ok(text)
+    }
+
+    /// Points XML prologs, meta charsets, coding cookies, modelines, CSS
+    /// `@charset` and gettext headers at the target encoding.
+    fn rewrite_declarations(text: &str, to: &str) -> String {
+        let target = Self::declaration_label(to);
+        // Declarations come grouped by kind, and one name can be matched by
+        // two kinds (a `# vim: set fileencoding=...` line is also a coding
+        // cookie), so order them by position and keep one of each overlap
+        let mut declarations = find_declarations(text.as_bytes());
+        declarations.sort_by_key(|d| d.range.start);
+        let mut end = 0;
+        declarations.retain(|d| {
+            let keep = d.range.start >= end
+                && text.is_char_boundary(d.range.start)
+                && text.is_char_boundary(d.range.end);
+            end = end.max(d.range.end);
+            keep
+        });
+
+        let mut result = text.to_string();
+        // Replace back to front so earlier ranges stay valid
+        for declaration in declarations.iter().rev() {
+            if resolve_label(&declaration.encoding) == resolve_label(target) {
+                continue;
+            }
+            let replacement = if declaration.encoding.chars().any(|c| c.is_ascii_uppercase()) {
+                target.to_string()
+            } else {
+                target.to_lowercase()
+            };
+            info!(
+                "Rewrote {} on line {} from {} to {}",
+                declaration.kind.describe(), declaration.line, declaration.encoding, replacement
+            );
+            result.replace_range(declaration.range.clone(), &replacement);
+        }
+        result
+    }
+
+    /// Java 9+ reads `.properties` files as UTF-8, older releases as
+    /// ISO-8859-1 with everything else written as `\uXXXX` escapes.
+    fn convert_properties_escapes(text: &str, to: &str) -> String {
+        let (result, count) = match to.to_uppercase().as_str() {
+            "ASCII" => properties::escape_above(text, 0x7F),
+            "ISO-8859-1" | "WINDOWS-1252" => properties::escape_above(text, 0xFF),
+            _ => {
+                let (result, count) = properties::unescape(text);
+                if count > 0 {
+                    info!("Unescaped {} \\uXXXX sequence(s)", count);
+                }
+                return result;
+            }
+        };
+        if count > 0 {
+            info!("Escaped {} character(s) as \\uXXXX", count);
+        }
+        result
+    }
+
+    fn declaration_label(encoding: &str) -> &'static str {
+        match encoding.to_uppercase().as_str() {
+            "UTF-16LE" | "UTF-16BE" => "UTF-16",
+            "WINDOWS-1252" => "windows-1252",
+            "ISO-8859-1" => "ISO-8859-1",
+            "ASCII" => "US-ASCII",
+            _ => "UTF-8",
+        }
+    }
+
     fn convert_to_unix_endings(text: &str) -> String {
         // First convert all Windows line endings (\r\n) to Unix (\n)
         let text = text.replace("\r\n", "\n");
@@ -98,10 +298,9 @@ impl EncodingConverter {
         unix_text.replace('\n', "\r\n")
     }
 
-    fn get_codecs(from: &FileEncoding, to: &str) -> Result<(&'static Encoding, &'static Encoding), ConversionError> {
-        let source_enc = Self::get_encoding(&from.encoding)?;
-        let target_enc = Self::get_encoding(to)?;
-        Ok((source_enc, target_enc))
+    /// Whether files can be converted from or to the named encoding.
+    pub fn supports(name: &str) -> bool {
+        Self::get_encoding(name).is_ok()
     }
 
     fn get_encoding(name: &str) -> Result<&'static Encoding, ConversionError> {
@@ -127,29 +326,187 @@ impl EncodingConverter {
         }
     }
 
-    pub fn convert_file(
+    /// Converts a file in memory, recompressing with the codec it was read
+    /// with. Returns None when the converted content would be byte-identical
+    /// to the input (e.g. ASCII going to UTF-8 without a BOM), so the file
+    /// can be left alone.
+    pub fn prepare_file(
         input_path: &std::path::Path,
-        output_path: &std::path::Path,
         from: &FileEncoding,
         to: &str,
-        line_ending: LineEnding,
-    ) -> Result<(), ConversionError> {
+        options: &ConversionOptions,
+    ) -> Result<Option<Vec<u8>>, ConversionError> {
         // Read input file
-        let mut input = Vec::new();
-        let mut file = std::fs::File::open(input_path)
-            .map_err(|e| ConversionError::IoError(e))?;
-        file.read_to_end(&mut input)
-            .map_err(|e| ConversionError::IoError(e))?;
+        let (input, codec) = compression::read_file(input_path, options.decompress)
+            .map_err(ConversionError::IoError)?;
 
         // Convert content
-        let output = Self::convert(&input, from, to, line_ending)?;
+        let mut output = Self::convert(&input, from, to, options)?;
+        if output == input {
+            return Ok(None);
+        }
+        if let Some(codec) = codec {
+            output = codec.compress(&output)
+                .map_err(ConversionError::IoError)?;
+            info!("Recompressed output with {}", codec.as_str());
+        }
+        Ok(Some(output))
+    }
 
-        // Write output file
+    pub fn write_file(output_path: &std::path::Path, output: &[u8]) -> Result<(), ConversionError> {
         let mut file = std::fs::File::create(output_path)
-            .map_err(|e| ConversionError::IoError(e))?;
-        file.write_all(&output)
-            .map_err(|e| ConversionError::IoError(e))?;
+            .map_err(ConversionError::IoError)?;
+        file.write_all(output)
+            .map_err(ConversionError::IoError)?;
 
         Ok(())
     }
 }
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ConversionStatus {
+    Converted,
+    AlreadyCompliant,
+    Skipped,
+    Failed,
+}
+
+impl ConversionStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ConversionStatus::Converted => "converted",
+            ConversionStatus::AlreadyCompliant => "already-compliant",
+            ConversionStatus::Skipped => "skipped",
+            ConversionStatus::Failed => "failed",
+        }
+    }
+}
+
+/// Outcome of converting one file.
+#[derive(Debug, Clone, Serialize)]
+pub struct ConversionResult {
+    #[serde(serialize_with = "serialize_path")]
+    pub path: PathBuf,
+    pub status: ConversionStatus,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<FileError>,
+}
+
+impl ConversionResult {
+    pub fn new(path: &std::path::Path, status: ConversionStatus) -> Self {
+        ConversionResult { path: path.to_path_buf(), status, error: None }
+    }
+
+    pub fn with_error(path: &std::path::Path, status: ConversionStatus, error: FileError) -> Self {
+        ConversionResult { error: Some(error), ..Self::new(path, status) }
+    }
+}
+
+impl From<&ConversionError> for FileError {
+    fn from(error: &ConversionError) -> Self {
+        match error {
+            ConversionError::IoError(e) => FileError::from(e),
+            ConversionError::RepairRejected(_) => FileError::new(ErrorKind::Verification, error.to_string()),
+            _ => FileError::new(ErrorKind::Encoding, error.to_string()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn options() -> ConversionOptions {
+        ConversionOptions {
+            line_ending: LineEnding::Keep,
+            repair_mixed: false,
+            repair_mojibake: 0,
+            mojibake_confidence: 0.6,
+            rewrite_declarations: true,
+            properties: false,
+            normalize: None,
+            strip_embedded_boms: false,
+            decompress: false,
+        }
+    }
+
+    fn latin1() -> FileEncoding {
+        FileEncoding { encoding: "ISO-8859-1".to_string(), bom: None }
+    }
+
+    fn utf8() -> FileEncoding {
+        FileEncoding { encoding: "UTF-8".to_string(), bom: None }
+    }
+
+    /// Converts Latin-1 `original` to UTF-8 and back, checking both sides.
+    fn round_trip(original: &str, converted: &str) {
+        let input = WINDOWS_1252.encode(original).0.into_owned();
+        let output = EncodingConverter::convert(&input, &latin1(), "UTF-8", &options()).unwrap();
+        assert_eq!(String::from_utf8(output.clone()).unwrap(), converted);
+
+        let back = EncodingConverter::convert(&output, &utf8(), "ISO-8859-1", &options()).unwrap();
+        let expected = original.replace("latin-1", "iso-8859-1").replace("latin1", "iso-8859-1");
+        assert_eq!(WINDOWS_1252.decode(&back).0, expected);
+    }
+
+    #[test]
+    fn rewrites_html_meta_charset() {
+        round_trip(
+            "<html><head><meta charset=\"ISO-8859-1\"></head><body>caf\u{e9}</body></html>",
+            "<html><head><meta charset=\"UTF-8\"></head><body>caf\u{e9}</body></html>",
+        );
+    }
+
+    #[test]
+    fn rewrites_xml_prolog_before_meta() {
+        round_trip(
+            "<?xml version=\"1.0\" encoding=\"ISO-8859-1\"?>\n<html><head><meta charset=\"ISO-8859-1\"></head></html>\n",
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<html><head><meta charset=\"UTF-8\"></head></html>\n",
+        );
+    }
+
+    #[test]
+    fn rewrites_meta_after_modeline() {
+        // Found in kind order (meta before vim), not in position order
+        round_trip(
+            "<!-- vim: set fenc=latin1 : -->\n<meta charset=\"ISO-8859-1\">\n",
+            "<!-- vim: set fenc=utf-8 : -->\n<meta charset=\"UTF-8\">\n",
+        );
+    }
+
+    #[test]
+    fn rewrites_python_cookie() {
+        round_trip(
+            "# -*- coding: ISO-8859-1 -*-\nname = \"J\u{fc}rgen\"\n",
+            "# -*- coding: UTF-8 -*-\nname = \"J\u{fc}rgen\"\n",
+        );
+    }
+
+    #[test]
+    fn rewrites_vim_modeline_matched_twice() {
+        // Matched both as a coding cookie and as a vim modeline
+        round_trip(
+            "# vim: set fileencoding=latin-1 :\nname = \"J\u{fc}rgen\"\n",
+            "# vim: set fileencoding=utf-8 :\nname = \"J\u{fc}rgen\"\n",
+        );
+    }
+
+    #[test]
+    fn repairs_mixed_utf8_and_windows_1252() {
+        let mut input = "caf\u{e9}\n".as_bytes().to_vec();
+        input.extend(b"caf\xe9 \x93quoted\x94\n");
+        let repair = ConversionOptions { repair_mixed: true, ..options() };
+        let output = EncodingConverter::convert(&input, &utf8(), "UTF-8", &repair).unwrap();
+        assert_eq!(String::from_utf8(output).unwrap(), "caf\u{e9}\ncaf\u{e9} \u{201c}quoted\u{201d}\n");
+
+        // Without the repair the legacy lines do not decode as UTF-8
+        assert!(EncodingConverter::convert(&input, &utf8(), "UTF-8", &options()).is_err());
+    }
+
+    #[test]
+    fn keeps_matching_declarations() {
+        let text = "<meta charset=\"utf-8\">";
+        assert_eq!(EncodingConverter::rewrite_declarations(text, "UTF-8"), text);
+    }
+}
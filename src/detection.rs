@@ -1,7 +1,8 @@
-use std::path::Path;
-use std::fs;
+use std::ops::Range;
 use chardet::{detect, charset2encoding};
+use encoding_rs::{Encoding, WINDOWS_1252};
 use serde::Serialize;
+use crate::findings::{byte_position, Finding, Severity};
 
 #[derive(Debug, Clone, Eq, PartialEq, Hash, Serialize)]
 pub struct FileEncoding {
@@ -46,75 +47,687 @@ fn looks_like_windows1252_or_iso8859_1(content: &[u8]) -> bool {
     content.iter().any(|&b| matches!(b, 0xA0..=0xFF))
 }
 
-pub fn detect_encoding(path: &Path) -> FileEncoding {
-    match fs::read(path) {
-        Ok(content) => {
-            if content.is_empty() {
-                return FileEncoding {
-                    encoding: "empty file".to_string(),
-                    bom: None,
-                };
+pub fn detect_encoding_from_bytes(content: &[u8]) -> FileEncoding {
+    detect_encoding_with_confidence(content).0
+}
+
+/// Detects the encoding along with how sure the detection is, from 0 to 1.
+/// A BOM, pure ASCII and valid UTF-8 are certain; anything else carries
+/// chardet's confidence.
+pub fn detect_encoding_with_confidence(content: &[u8]) -> (FileEncoding, f64) {
+    if content.is_empty() {
+        return (FileEncoding {
+            encoding: "empty file".to_string(),
+            bom: None,
+        }, 1.0);
+    }
+
+    // First check for BOM
+    if let Some(bom_info) = detect_bom(content) {
+        return (FileEncoding {
+            encoding: bom_info.bom_type.to_string(),
+            bom: Some(bom_info.bom_type),
+        }, 1.0);
+    }
+
+    // If no BOM, try to detect encoding
+    let content_without_bom = if let Some(bom_info) = detect_bom(content) {
+        &content[bom_info.skip_bytes..]
+    } else {
+        content
+    };
+
+    // Check for ASCII first (subset of UTF-8)
+    if is_ascii(content_without_bom) {
+        return (FileEncoding {
+            encoding: "ASCII".to_string(),
+            bom: None,
+        }, 1.0);
+    }
+
+    // Check for UTF-8 without BOM
+    if is_utf8(content_without_bom) {
+        return (FileEncoding {
+            encoding: "UTF-8".to_string(),
+            bom: None,
+        }, 1.0);
+    }
+
+    // Use chardet for additional detection
+    let detect_result = detect(content_without_bom);
+    let chardet_encoding = charset2encoding(&detect_result.0).to_string();
+    let confidence = f64::from(detect_result.1);
+
+    // Special handling for Windows-1252 and ISO-8859-1
+    if (chardet_encoding == "ISO-8859-1" || chardet_encoding == "windows-1252")
+        && looks_like_windows1252_or_iso8859_1(content_without_bom)
+    {
+        return (FileEncoding {
+            encoding: legacy_encoding_name(content_without_bom).to_string(),
+            bom: None,
+        }, confidence);
+    }
+
+    // Return chardet result if no other encoding was detected
+    (FileEncoding {
+        encoding: chardet_encoding,
+        bom: None,
+    }, confidence)
+}
+
+pub fn legacy_encoding_name(content: &[u8]) -> &'static str {
+    if content.iter().any(|&b| matches!(b, 0x80..=0x9F)) {
+        "windows-1252"
+    } else {
+        "ISO-8859-1"
+    }
+}
+
+// Beyond this many NUL runs the file is more likely binary than damaged text
+const MAX_NUL_RUN_FINDINGS: usize = 10;
+
+/// Reports structural damage that still leaves the content decodable:
+/// byte order marks left behind by concatenation, NUL runs from truncated
+/// writes and multi-byte sequences cut off at the end of the file.
+pub fn detect_anomalies(content: &[u8], encoding: &FileEncoding) -> Vec<Finding> {
+    let mut findings = Vec::new();
+    let name = encoding.encoding.to_uppercase();
+    let (bom, unit): (&[u8], usize) = match name.as_str() {
+        "UTF-16LE" => (&[0xFF, 0xFE], 2),
+        "UTF-16BE" => (&[0xFE, 0xFF], 2),
+        _ if name.starts_with("UTF-32") => return findings,
+        "EMPTY FILE" | "BINARY/UNREADABLE" => return findings,
+        _ => (&[0xEF, 0xBB, 0xBF], 1),
+    };
+    let wide = unit > 1;
+
+    // In legacy 8-bit encodings the UTF-8 BOM bytes are ordinary text ("ï»¿").
+    // Only a leading BOM is where it belongs
+    let mut offset = if !wide && name != "UTF-8" {
+        content.len()
+    } else if content.starts_with(bom) {
+        bom.len()
+    } else {
+        0
+    };
+    while offset + bom.len() <= content.len() {
+        if &content[offset..offset + bom.len()] == bom {
+            let mut finding = Finding::new(
+                "embedded-bom",
+                Severity::Warning,
+                format!("byte order mark at byte {}", offset),
+            );
+            if !wide {
+                finding = finding.at(byte_position(content, offset));
             }
+            findings.push(finding);
+            offset += bom.len();
+        } else {
+            offset += unit;
+        }
+    }
+
+    if wide {
+        if !content.len().is_multiple_of(2) {
+            findings.push(Finding::new(
+                "truncated-sequence",
+                Severity::Error,
+                format!("{} content has an odd number of bytes", encoding.encoding),
+            ));
+        }
+        return findings;
+    }
+
+    let mut runs = 0;
+    let mut offset = 0;
+    while let Some(start) = content[offset..].iter().position(|&b| b == 0).map(|p| offset + p) {
+        let len = content[start..].iter().take_while(|&&b| b == 0).count();
+        runs += 1;
+        if runs <= MAX_NUL_RUN_FINDINGS {
+            findings.push(Finding::new(
+                "nul-run",
+                Severity::Warning,
+                format!("{} NUL byte(s) at byte {}", len, start),
+            ).at(byte_position(content, start)));
+        }
+        offset = start + len;
+    }
+    if runs > MAX_NUL_RUN_FINDINGS {
+        findings.push(Finding::new(
+            "nul-run",
+            Severity::Warning,
+            format!("{} further NUL run(s) not listed", runs - MAX_NUL_RUN_FINDINGS),
+        ));
+    }
+
+    // A UTF-8 file whose only flaw is an incomplete sequence at the very end
+    if let Err(e) = std::str::from_utf8(content) {
+        if e.error_len().is_none() && e.valid_up_to() > 0 {
+            findings.push(Finding::new(
+                "truncated-sequence",
+                Severity::Error,
+                "file ends in the middle of a UTF-8 multi-byte sequence".to_string(),
+            ).at(byte_position(content, e.valid_up_to())));
+        }
+    }
+    findings
+}
+
+/// A run of lines that share one encoding inside a file.
+#[derive(Debug, Clone, Serialize)]
+pub struct EncodingSegment {
+    pub encoding: String,
+    pub start_line: usize,
+    pub end_line: usize,
+    pub start_byte: usize,
+    pub end_byte: usize,
+}
+
+/// Splits content into line ranges that are each valid UTF-8 or legacy
+/// (Windows-1252/ISO-8859-1) text. Pure ASCII lines belong to whichever
+/// segment they are adjacent to. A single segment means the file is not mixed.
+pub fn detect_segments(content: &[u8]) -> Vec<EncodingSegment> {
+    let mut segments: Vec<EncodingSegment> = Vec::new();
+    let mut pending_start: Option<(usize, usize)> = None;
+    let mut offset = 0;
+
+    for (index, line) in content.split_inclusive(|&b| b == b'\n').enumerate() {
+        let line_no = index + 1;
+        let end = offset + line.len();
+        let class = if is_ascii(line) {
+            None
+        } else if is_utf8(line) {
+            Some("UTF-8")
+        } else {
+            Some("legacy")
+        };
 
-            // First check for BOM
-            if let Some(bom_info) = detect_bom(&content) {
-                return FileEncoding {
-                    encoding: bom_info.bom_type.to_string(),
-                    bom: Some(bom_info.bom_type),
-                };
+        match (class, segments.last_mut()) {
+            (None, Some(last)) => {
+                last.end_line = line_no;
+                last.end_byte = end;
             }
+            (None, None) => {
+                pending_start.get_or_insert((line_no, offset));
+            }
+            (Some(class), Some(last)) if last.encoding == class => {
+                last.end_line = line_no;
+                last.end_byte = end;
+            }
+            (Some(class), _) => {
+                let (start_line, start_byte) = pending_start.take().unwrap_or((line_no, offset));
+                segments.push(EncodingSegment {
+                    encoding: class.to_string(),
+                    start_line,
+                    end_line: line_no,
+                    start_byte,
+                    end_byte: end,
+                });
+            }
+        }
+        offset = end;
+    }
+
+    if segments.is_empty() {
+        let lines = content.split_inclusive(|&b| b == b'\n').count();
+        segments.push(EncodingSegment {
+            encoding: "ASCII".to_string(),
+            start_line: 1,
+            end_line: lines.max(1),
+            start_byte: 0,
+            end_byte: content.len(),
+        });
+    }
+
+    for segment in &mut segments {
+        if segment.encoding == "legacy" {
+            let bytes = &content[segment.start_byte..segment.end_byte];
+            segment.encoding = legacy_encoding_name(bytes).to_string();
+        }
+    }
+    segments
+}
+
+/// The syntax a file used to declare its own character encoding.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum DeclarationKind {
+    XmlProlog,
+    HtmlMeta,
+    PythonCookie,
+    EmacsModeline,
+    VimModeline,
+    CssCharset,
+    GettextHeader,
+}
+
+impl DeclarationKind {
+    pub fn describe(&self) -> &'static str {
+        match self {
+            DeclarationKind::XmlProlog => "XML prolog",
+            DeclarationKind::HtmlMeta => "HTML meta charset",
+            DeclarationKind::PythonCookie => "Python coding cookie",
+            DeclarationKind::EmacsModeline => "Emacs modeline",
+            DeclarationKind::VimModeline => "Vim modeline",
+            DeclarationKind::CssCharset => "CSS @charset",
+            DeclarationKind::GettextHeader => "gettext Content-Type header",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct EncodingDeclaration {
+    pub kind: DeclarationKind,
+    pub encoding: String,
+    pub line: usize,
+    pub column: usize,
+    /// Byte range of the encoding name within the scanned content
+    #[serde(skip)]
+    pub range: Range<usize>,
+}
+
+// HTML parsers only prescan this many bytes for a meta charset
+const HTML_PRESCAN_BYTES: usize = 1024;
+// Vim looks for modelines in this many lines at the start and end of a file
+const VIM_MODELINE_LINES: usize = 5;
 
-            // If no BOM, try to detect encoding
-            let content_without_bom = if let Some(bom_info) = detect_bom(&content) {
-                &content[bom_info.skip_bytes..]
+/// Finds encoding declarations in ASCII-compatible content. Content with a
+/// UTF-16/32 BOM is skipped since declarations there are not byte-searchable.
+pub fn find_declarations(content: &[u8]) -> Vec<EncodingDeclaration> {
+    let start = match detect_bom(content) {
+        Some(bom) if bom.bom_type == "UTF-8" => bom.skip_bytes,
+        Some(_) => return Vec::new(),
+        None => 0,
+    };
+    let mut found: Vec<(DeclarationKind, Range<usize>)> = Vec::new();
+    let body = &content[start..];
+
+    if body.starts_with(b"<?xml") {
+        if let Some(end) = find_ci(body, b"?>") {
+            if let Some(pos) = find_ci(&body[..end], b"encoding") {
+                if let Some(range) = attribute_value(body, pos + 8) {
+                    found.push((DeclarationKind::XmlProlog, shift(range, start)));
+                }
+            }
+        }
+    }
+
+    if let Some(rest) = body.strip_prefix(b"@charset \"") {
+        if let Some(len) = rest.iter().position(|&b| b == b'"') {
+            let value = 10..10 + len;
+            found.push((DeclarationKind::CssCharset, shift(value, start)));
+        }
+    }
+
+    let prescan = &body[..body.len().min(HTML_PRESCAN_BYTES)];
+    let mut offset = 0;
+    while let Some(pos) = find_ci(&prescan[offset..], b"<meta") {
+        let tag_start = offset + pos;
+        let tag_end = prescan[tag_start..].iter().position(|&b| b == b'>')
+            .map_or(prescan.len(), |p| tag_start + p);
+        if let Some(charset) = find_ci(&prescan[tag_start..tag_end], b"charset") {
+            if let Some(range) = attribute_value(prescan, tag_start + charset + 7) {
+                found.push((DeclarationKind::HtmlMeta, shift(range, start)));
+            }
+        }
+        offset = tag_end;
+    }
+
+    let lines: Vec<(usize, &[u8])> = line_offsets(body).collect();
+    for &(line_start, line) in lines.iter().take(2) {
+        if let Some(range) = coding_cookie(line) {
+            let kind = if line.trim_ascii_start().starts_with(b"#") {
+                DeclarationKind::PythonCookie
             } else {
-                &content
+                DeclarationKind::EmacsModeline
             };
+            found.push((kind, shift(range, start + line_start)));
+        }
+    }
+
+    let tail_start = lines.len().saturating_sub(VIM_MODELINE_LINES).max(VIM_MODELINE_LINES);
+    let vim_lines = lines.iter().take(VIM_MODELINE_LINES).chain(lines.iter().skip(tail_start));
+    for &(line_start, line) in vim_lines {
+        if let Some(range) = vim_fileencoding(line) {
+            found.push((DeclarationKind::VimModeline, shift(range, start + line_start)));
+        }
+    }
 
-            // Check for ASCII first (subset of UTF-8)
-            if is_ascii(content_without_bom) {
-                return FileEncoding {
-                    encoding: "ASCII".to_string(),
-                    bom: None,
-                };
+    // PO files declare their charset in the header entry's msgstr
+    if let Some(&(line_start, line)) = lines.iter().find(|(_, line)| line.starts_with(b"\"Content-Type:")) {
+        if let Some(pos) = find_ci(line, b"charset") {
+            if let Some(range) = attribute_value(line, pos + 7) {
+                found.push((DeclarationKind::GettextHeader, shift(range, start + line_start)));
             }
+        }
+    }
 
-            // Check for UTF-8 without BOM
-            if is_utf8(content_without_bom) {
-                return FileEncoding {
-                    encoding: "UTF-8".to_string(),
-                    bom: None,
-                };
+    found.into_iter()
+        .map(|(kind, range)| {
+            let line_start = content[..range.start].iter().rposition(|&b| b == b'\n').map_or(0, |p| p + 1);
+            EncodingDeclaration {
+                kind,
+                encoding: String::from_utf8_lossy(&content[range.clone()]).into_owned(),
+                line: content[..range.start].iter().filter(|&&b| b == b'\n').count() + 1,
+                column: range.start - line_start + 1,
+                range,
             }
+        })
+        .collect()
+}
 
-            // Use chardet for additional detection
-            let detect_result = detect(content_without_bom);
-            let chardet_encoding = charset2encoding(&detect_result.0).to_string();
-
-            // Special handling for Windows-1252 and ISO-8859-1
-            if chardet_encoding == "ISO-8859-1" || chardet_encoding == "windows-1252" {
-                if looks_like_windows1252_or_iso8859_1(content_without_bom) {
-                    let encoding = if content_without_bom.iter().any(|&b| matches!(b, 0x80..=0x9F)) {
-                        "windows-1252"
-                    } else {
-                        "ISO-8859-1"
-                    };
-                    return FileEncoding {
-                        encoding: encoding.to_string(),
-                        bom: None,
-                    };
-                }
+fn shift(range: Range<usize>, by: usize) -> Range<usize> {
+    range.start + by..range.end + by
+}
+
+fn line_offsets(content: &[u8]) -> impl Iterator<Item = (usize, &[u8])> {
+    let mut offset = 0;
+    content.split_inclusive(|&b| b == b'\n').map(move |line| {
+        let start = offset;
+        offset += line.len();
+        (start, line)
+    })
+}
+
+fn find_ci(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack.windows(needle.len()).position(|w| w.eq_ignore_ascii_case(needle))
+}
+
+fn token_end(content: &[u8], start: usize) -> usize {
+    start + content[start..].iter()
+        .take_while(|b| b.is_ascii_alphanumeric() || matches!(b, b'-' | b'_' | b'.'))
+        .count()
+}
+
+fn skip_blanks(content: &[u8], mut pos: usize) -> usize {
+    while pos < content.len() && matches!(content[pos], b' ' | b'\t' | b'\r' | b'\n') {
+        pos += 1;
+    }
+    pos
+}
+
+/// Reads `= value`, `="value"` or `='value'` starting right after a key.
+fn attribute_value(content: &[u8], key_end: usize) -> Option<Range<usize>> {
+    let pos = skip_blanks(content, key_end);
+    if content.get(pos) != Some(&b'=') {
+        return None;
+    }
+    let mut pos = skip_blanks(content, pos + 1);
+    if matches!(content.get(pos), Some(b'"') | Some(b'\'')) {
+        pos += 1;
+    }
+    let end = token_end(content, pos);
+    (end > pos).then_some(pos..end)
+}
+
+/// Matches PEP 263 cookies and Emacs `-*- coding: name -*-` lines.
+fn coding_cookie(line: &[u8]) -> Option<Range<usize>> {
+    let is_comment = line.trim_ascii_start().starts_with(b"#");
+    if !is_comment && find_ci(line, b"-*-").is_none() {
+        return None;
+    }
+    let mut offset = 0;
+    while let Some(pos) = find_ci(&line[offset..], b"coding") {
+        let after = offset + pos + 6;
+        if matches!(line.get(after), Some(b':') | Some(b'=')) {
+            let start = after + 1 + line[after + 1..].iter().take_while(|b| matches!(b, b' ' | b'\t')).count();
+            let end = token_end(line, start);
+            if end > start {
+                return Some(start..end);
             }
+        }
+        offset = after;
+    }
+    None
+}
 
-            // Return chardet result if no other encoding was detected
-            FileEncoding {
-                encoding: chardet_encoding,
-                bom: None,
+fn vim_fileencoding(line: &[u8]) -> Option<Range<usize>> {
+    let has_marker = [b"vim:".as_slice(), b"vi:", b"ex:"].iter().any(|marker| {
+        find_ci(line, marker).is_some_and(|pos| pos == 0 || line[pos - 1].is_ascii_whitespace())
+    });
+    if !has_marker {
+        return None;
+    }
+    for key in [b"fileencoding=".as_slice(), b"fenc="] {
+        if let Some(pos) = find_ci(line, key) {
+            let start = pos + key.len();
+            let end = token_end(line, start);
+            if end > start {
+                return Some(start..end);
             }
         }
-        Err(_) => FileEncoding {
-            encoding: "binary/unreadable".to_string(),
-            bom: None,
+    }
+    None
+}
+
+/// Resolves a declared encoding name, accepting the Python and Emacs
+/// spellings that the WHATWG label table does not know.
+pub fn resolve_label(label: &str) -> Option<&'static Encoding> {
+    let label = label.trim().to_ascii_lowercase().replace('_', "-");
+    let label = label.trim_end_matches("-unix").trim_end_matches("-dos").trim_end_matches("-mac");
+    let label = match label {
+        "latin-1" | "iso-latin-1" => "latin1",
+        other => other,
+    };
+    Encoding::for_label(label.as_bytes())
+}
+
+/// The repo's name for a declared encoding, as understood by the converter.
+pub fn declared_encoding_name(label: &str) -> Option<String> {
+    let encoding = resolve_label(label)?;
+    let lower = label.to_ascii_lowercase();
+    if encoding == WINDOWS_1252 && (lower.contains("8859-1") || lower.contains("8859_1") || lower.contains("latin")) {
+        return Some("ISO-8859-1".to_string());
+    }
+    Some(encoding.name().to_string())
+}
+
+/// Whether a declared encoding agrees with the detected one.
+/// Returns None when either side cannot be compared.
+pub fn declaration_matches(declared: &str, detected: &FileEncoding) -> Option<bool> {
+    let declared = resolve_label(declared)?;
+    match detected.encoding.as_str() {
+        "empty file" | "binary/unreadable" => None,
+        // Pure ASCII content is valid in every ASCII-compatible encoding
+        "ASCII" => Some(declared.is_ascii_compatible()),
+        other => {
+            let detected = resolve_label(other)?;
+            let is_utf16 = |e: &'static Encoding| e == encoding_rs::UTF_16LE || e == encoding_rs::UTF_16BE;
+            Some(declared == detected || (is_utf16(declared) && is_utf16(detected)))
+        }
+    }
+}
+
+/// Uses a conflicting declaration as the encoding if the content decodes
+/// cleanly with it. A BOM always takes precedence over declarations.
+pub fn apply_declaration_hint(
+    content: &[u8],
+    detected: &FileEncoding,
+    declarations: &[EncodingDeclaration],
+) -> Option<(FileEncoding, DeclarationKind)> {
+    if detected.bom.is_some() {
+        return None;
+    }
+    declarations.iter()
+        .filter(|d| declaration_matches(&d.encoding, detected) == Some(false))
+        .find_map(|d| {
+            let encoding = resolve_label(&d.encoding).filter(|e| e.is_ascii_compatible())?;
+            let (_, had_errors) = encoding.decode_without_bom_handling(content);
+            if had_errors {
+                return None;
+            }
+            Some((FileEncoding {
+                encoding: declared_encoding_name(&d.encoding)?,
+                bom: None,
+            }, d.kind))
+        })
+}
+
+/// Line break convention used by a text, reported only when it has line breaks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum LineEndingStyle {
+    #[serde(rename = "LF")]
+    Lf,
+    #[serde(rename = "CRLF")]
+    Crlf,
+    #[serde(rename = "CR")]
+    Cr,
+    #[serde(rename = "mixed")]
+    Mixed,
+}
+
+impl LineEndingStyle {
+    pub fn from_str(s: &str) -> Option<Self> {
+        match s.to_lowercase().as_str() {
+            "lf" | "unix" => Some(LineEndingStyle::Lf),
+            "crlf" | "windows" => Some(LineEndingStyle::Crlf),
+            "cr" => Some(LineEndingStyle::Cr),
+            "mixed" => Some(LineEndingStyle::Mixed),
+            _ => None,
         }
     }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            LineEndingStyle::Lf => "LF",
+            LineEndingStyle::Crlf => "CRLF",
+            LineEndingStyle::Cr => "CR",
+            LineEndingStyle::Mixed => "mixed",
+        }
+    }
+}
+
+pub fn detect_line_endings(text: &str) -> Option<LineEndingStyle> {
+    let crlf = text.matches("\r\n").count();
+    let lf = text.matches('\n').count() - crlf;
+    let cr = text.matches('\r').count() - crlf;
+    match (lf > 0, crlf > 0, cr > 0) {
+        (false, false, false) => None,
+        (true, false, false) => Some(LineEndingStyle::Lf),
+        (false, true, false) => Some(LineEndingStyle::Crlf),
+        (false, false, true) => Some(LineEndingStyle::Cr),
+        _ => Some(LineEndingStyle::Mixed),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn found(content: &str) -> Vec<(DeclarationKind, String, usize, usize)> {
+        find_declarations(content.as_bytes()).into_iter()
+            .map(|d| (d.kind, d.encoding, d.line, d.column))
+            .collect()
+    }
+
+    /// A UTF-8 section followed by a Windows-1252 one, with ASCII lines
+    /// before and between them.
+    fn mixed_content() -> Vec<u8> {
+        let mut content = b"header\n".to_vec();
+        content.extend("caf\u{e9}\nna\u{ef}ve\nplain\n".as_bytes());
+        content.extend(b"caf\xe9\n\x93quoted\x94\n");
+        content
+    }
+
+    #[test]
+    fn splits_mixed_content_into_segments() {
+        let content = mixed_content();
+        let legacy_start = content.len() - b"caf\xe9\n\x93quoted\x94\n".len();
+        let segments: Vec<_> = detect_segments(&content).into_iter()
+            .map(|s| (s.encoding, s.start_line, s.end_line, s.start_byte, s.end_byte))
+            .collect();
+        assert_eq!(segments, [
+            ("UTF-8".to_string(), 1, 4, 0, legacy_start),
+            ("windows-1252".to_string(), 5, 6, legacy_start, content.len()),
+        ]);
+    }
+
+    #[test]
+    fn keeps_unmixed_content_in_one_segment() {
+        let segments = detect_segments("caf\u{e9}\nplain\n".as_bytes());
+        assert_eq!(segments.len(), 1);
+        assert_eq!(segments[0].encoding, "UTF-8");
+        let segments = detect_segments(b"one\ntwo");
+        assert_eq!((segments[0].encoding.as_str(), segments[0].end_line), ("ASCII", 2));
+    }
+
+    #[test]
+    fn finds_xml_prolog_and_meta() {
+        let content = "<?xml version=\"1.0\" encoding='ISO-8859-1'?>\n<meta charset=\"utf-8\">\n";
+        assert_eq!(found(content), vec![
+            (DeclarationKind::XmlProlog, "ISO-8859-1".to_string(), 1, 31),
+            (DeclarationKind::HtmlMeta, "utf-8".to_string(), 2, 16),
+        ]);
+    }
+
+    #[test]
+    fn finds_http_equiv_meta() {
+        let content = "<meta http-equiv=\"Content-Type\" content=\"text/html; charset=windows-1252\">";
+        assert_eq!(found(content), vec![(DeclarationKind::HtmlMeta, "windows-1252".to_string(), 1, 61)]);
+    }
+
+    #[test]
+    fn finds_css_charset_after_bom() {
+        let content = "\u{FEFF}@charset \"latin1\";\nbody {}\n";
+        let declarations = find_declarations(content.as_bytes());
+        assert_eq!(declarations.len(), 1);
+        assert_eq!(declarations[0].kind, DeclarationKind::CssCharset);
+        assert_eq!(&content.as_bytes()[declarations[0].range.clone()], b"latin1");
+    }
+
+    #[test]
+    fn finds_python_and_vim_declarations_on_one_line() {
+        let content = "# vim: set fileencoding=latin-1 :\n";
+        let declarations = find_declarations(content.as_bytes());
+        let kinds: Vec<_> = declarations.iter().map(|d| d.kind).collect();
+        assert_eq!(kinds, vec![DeclarationKind::PythonCookie, DeclarationKind::VimModeline]);
+        assert!(declarations.iter().all(|d| &content[d.range.clone()] == "latin-1"));
+    }
+
+    #[test]
+    fn finds_gettext_header() {
+        let content = "msgid \"\"\nmsgstr \"\"\n\"Content-Type: text/plain; charset=ISO-8859-15\\n\"\n";
+        assert_eq!(found(content), vec![(DeclarationKind::GettextHeader, "ISO-8859-15".to_string(), 3, 36)]);
+    }
+
+    fn encoding(name: &str) -> FileEncoding {
+        FileEncoding { encoding: name.to_string(), bom: None }
+    }
+
+    #[test]
+    fn reports_embedded_bom_in_utf8() {
+        let findings = detect_anomalies(b"first\n\xEF\xBB\xBFsecond\n", &encoding("UTF-8"));
+        assert_eq!(findings.len(), 1);
+        assert_eq!(findings[0].kind, "embedded-bom");
+        assert_eq!((findings[0].line, findings[0].column), (Some(2), Some(1)));
+    }
+
+    #[test]
+    fn reports_bom_near_the_start_but_not_a_leading_one() {
+        let findings = detect_anomalies(b"ab\xEF\xBB\xBFcd\n", &encoding("UTF-8"));
+        assert_eq!(findings.len(), 1);
+        assert_eq!((findings[0].line, findings[0].column), (Some(1), Some(3)));
+        assert!(detect_anomalies(b"\xEF\xBB\xBFcd\n", &encoding("UTF-8")).is_empty());
+    }
+
+    #[test]
+    fn reports_embedded_bom_in_utf16() {
+        let findings = detect_anomalies(b"\xFF\xFEa\0\xFF\xFEb\0", &encoding("UTF-16LE"));
+        assert_eq!(findings.len(), 1);
+        assert_eq!(findings[0].message, "byte order mark at byte 4");
+        assert_eq!(detect_anomalies(b"a\0\xFF\xFEb\0", &encoding("UTF-16LE")).len(), 1);
+    }
+
+    #[test]
+    fn bom_bytes_are_text_in_legacy_encodings() {
+        // "ï»¿" is a legitimate string in windows-1252
+        let content = b"caf\xE9 \xEF\xBB\xBF\n";
+        assert!(detect_anomalies(content, &encoding("windows-1252")).is_empty());
+        assert!(detect_anomalies(content, &encoding("ISO-8859-1")).is_empty());
+    }
+
+    #[test]
+    fn ignores_utf16_content() {
+        assert!(find_declarations(b"\xFF\xFE<\0?\0x\0m\0l\0").is_empty());
+    }
 }
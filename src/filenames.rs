@@ -0,0 +1,160 @@
+use std::ffi::OsStr;
+use std::path::{Path, PathBuf};
+use serde::Serialize;
+use unicode_normalization::{is_nfc, UnicodeNormalization};
+use crate::detection::{declared_encoding_name, detect_encoding_from_bytes, legacy_encoding_name};
+
+/// A file or directory name that is not valid UTF-8, or not in NFC when
+/// NFC names were requested.
+#[derive(Serialize, Clone)]
+pub struct FilenameIssue {
+    #[serde(serialize_with = "serialize_path")]
+    pub path: PathBuf,
+    pub reason: &'static str,
+    pub likely_encoding: String,
+    pub suggested_name: String,
+}
+
+impl FilenameIssue {
+    pub fn suggested_path(&self) -> PathBuf {
+        self.path.with_file_name(&self.suggested_name)
+    }
+}
+
+pub fn check_name(path: &Path, require_nfc: bool) -> Option<FilenameIssue> {
+    let name = path.file_name()?;
+    if let Some(name) = name.to_str() {
+        if require_nfc && !is_nfc(name) {
+            return Some(FilenameIssue {
+                path: path.to_path_buf(),
+                reason: "not NFC",
+                likely_encoding: "UTF-8".to_string(),
+                suggested_name: name.nfc().collect(),
+            });
+        }
+        return None;
+    }
+
+    let bytes = name_bytes(name)?;
+    let (encoding, decoded) = guess_encoding(bytes);
+    let suggested_name = if require_nfc { decoded.nfc().collect() } else { decoded };
+    Some(FilenameIssue {
+        path: path.to_path_buf(),
+        reason: "not UTF-8",
+        likely_encoding: encoding,
+        suggested_name,
+    })
+}
+
+/// Chardet is unreliable on strings as short as file names, so its guess
+/// competes with the Latin-1 family that old archives almost always use.
+/// The decoding that yields the most letters wins, Latin-1 on a tie.
+fn guess_encoding(bytes: &[u8]) -> (String, String) {
+    let chardet_guess = detect_encoding_from_bytes(bytes).encoding;
+    let candidates = [legacy_encoding_name(bytes), chardet_guess.as_str()];
+
+    let mut best: Option<(usize, String, String)> = None;
+    for candidate in candidates {
+        let Some(name) = declared_encoding_name(candidate) else { continue };
+        let Some(encoding) = encoding_rs::Encoding::for_label(name.as_bytes()) else { continue };
+        let (decoded, had_errors) = encoding.decode_without_bom_handling(bytes);
+        if had_errors || decoded.contains(char::is_control) {
+            continue;
+        }
+        let letters = decoded.chars().filter(|c| !c.is_ascii() && c.is_alphabetic()).count();
+        if best.as_ref().is_none_or(|(score, _, _)| letters > *score) {
+            best = Some((letters, name, decoded.into_owned()));
+        }
+    }
+    match best {
+        Some((_, name, decoded)) => (name, decoded),
+        None => ("unknown".to_string(), String::from_utf8_lossy(bytes).into_owned()),
+    }
+}
+
+#[cfg(unix)]
+fn name_bytes(name: &OsStr) -> Option<&[u8]> {
+    use std::os::unix::ffi::OsStrExt;
+    Some(name.as_bytes())
+}
+
+// Windows file names are UTF-16 and cannot carry a legacy 8-bit encoding
+#[cfg(not(unix))]
+fn name_bytes(_name: &OsStr) -> Option<&[u8]> {
+    None
+}
+
+/// Printable form of a path that keeps undecodable bytes visible as \xNN
+/// instead of collapsing them into replacement characters.
+pub fn escaped_path(path: &Path) -> String {
+    if let Some(s) = path.to_str() {
+        return s.to_string();
+    }
+    let Some(bytes) = name_bytes(path.as_os_str()) else {
+        return path.to_string_lossy().into_owned();
+    };
+    let mut result = String::with_capacity(bytes.len());
+    for chunk in bytes.utf8_chunks() {
+        result.push_str(chunk.valid());
+        for byte in chunk.invalid() {
+            result.push_str(&format!("\\x{:02X}", byte));
+        }
+    }
+    result
+}
+
+/// Relative URI reference for a path, with every byte outside the unreserved
+/// set percent-encoded so undecodable names survive intact.
+pub fn path_uri(path: &Path) -> String {
+    let lossy = path.to_string_lossy().replace('\\', "/");
+    let bytes = name_bytes(path.as_os_str()).unwrap_or(lossy.as_bytes());
+    let mut uri = String::with_capacity(bytes.len());
+    for &byte in bytes {
+        if byte.is_ascii_alphanumeric() || b"-._~/".contains(&byte) {
+            uri.push(byte as char);
+        } else {
+            uri.push_str(&format!("%{:02X}", byte));
+        }
+    }
+    uri
+}
+
+/// Serializes paths with `escaped_path`, since serde rejects non-UTF-8 paths.
+pub fn serialize_path<S: serde::Serializer>(path: &Path, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(&escaped_path(path))
+}
+
+pub fn serialize_optional_path<S: serde::Serializer>(
+    path: &Option<PathBuf>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    match path {
+        Some(path) => serialize_path(path, serializer),
+        None => serializer.serialize_none(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn path_uri_keeps_unreserved_characters() {
+        assert_eq!(path_uri(Path::new("src/main_1.rs")), "src/main_1.rs");
+        assert_eq!(path_uri(Path::new("a-b/.c~d")), "a-b/.c~d");
+    }
+
+    #[test]
+    fn path_uri_percent_encodes_everything_else() {
+        assert_eq!(path_uri(Path::new("my docs/50%#1?.txt")), "my%20docs/50%25%231%3F.txt");
+        assert_eq!(path_uri(Path::new("café.txt")), "caf%C3%A9.txt");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn path_uri_keeps_undecodable_bytes() {
+        use std::os::unix::ffi::OsStrExt;
+        let path = Path::new(OsStr::from_bytes(b"caf\xe9.txt"));
+        assert_eq!(path_uri(path), "caf%E9.txt");
+    }
+}
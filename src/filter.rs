@@ -1,7 +1,57 @@
+use std::fs::Metadata;
 use std::path::Path;
+use std::time::SystemTime;
+use chrono::{DateTime, Local, NaiveDate, TimeZone};
+
+/// A condition a scanned path has to meet, on top of the extension list.
+#[derive(Debug, Clone, Copy)]
+pub enum Predicate {
+    /// Deepest level to descend to; files directly in the root are at depth 1
+    MaxDepth(usize),
+    /// Leave out files and directories whose name starts with a dot
+    SkipHidden,
+    MinSize(u64),
+    MaxSize(u64),
+    ModifiedSince(SystemTime),
+    ModifiedBefore(SystemTime),
+}
+
+impl Predicate {
+    /// Whether a directory at `depth` can contain anything this predicate
+    /// accepts, so the scan can skip it without reading it.
+    fn enters(&self, dir: &Path, depth: usize) -> bool {
+        match self {
+            Predicate::MaxDepth(max) => depth < *max,
+            Predicate::SkipHidden => !is_hidden(dir),
+            _ => true,
+        }
+    }
+
+    /// Checks that only need the name, which also apply to archive entries.
+    /// Entries have no directories to skip while walking, so every
+    /// component of their path is checked.
+    fn accepts_entry(&self, path: &Path) -> bool {
+        match self {
+            Predicate::SkipHidden => !path.components().any(|c| is_hidden(Path::new(c.as_os_str()))),
+            _ => true,
+        }
+    }
+
+    fn accepts_file(&self, path: &Path, metadata: &Metadata, depth: usize) -> bool {
+        match self {
+            Predicate::MaxDepth(max) => depth <= *max,
+            Predicate::SkipHidden => !is_hidden(path),
+            Predicate::MinSize(min) => metadata.len() >= *min,
+            Predicate::MaxSize(max) => metadata.len() <= *max,
+            Predicate::ModifiedSince(time) => metadata.modified().is_ok_and(|m| m >= *time),
+            Predicate::ModifiedBefore(time) => metadata.modified().is_ok_and(|m| m < *time),
+        }
+    }
+}
 
 pub struct FileFilter {
     extensions: Option<Vec<String>>,
+    predicates: Vec<Predicate>,
 }
 
 impl FileFilter {
@@ -15,10 +65,21 @@ impl FileFilter {
                 .collect()
         });
         
-        FileFilter { extensions }
+        FileFilter { extensions, predicates: Vec::new() }
     }
 
+    pub fn with(mut self, predicate: Predicate) -> Self {
+        self.predicates.push(predicate);
+        self
+    }
+
+    /// Whether an archive entry, named by its path inside the archive,
+    /// passes the extension list and the name-only predicates.
     pub fn should_include(&self, path: &Path) -> bool {
+        self.predicates.iter().all(|p| p.accepts_entry(path)) && self.matches_extension(path)
+    }
+
+    fn matches_extension(&self, path: &Path) -> bool {
         if let Some(extensions) = &self.extensions {
             if let Some(ext) = path.extension() {
                 if let Some(ext_str) = ext.to_str() {
@@ -30,4 +91,125 @@ impl FileFilter {
             true // No filter means include all files
         }
     }
+
+    /// Whether a file found at `depth` below the scan root passes the
+    /// extension list and every predicate.
+    pub fn should_include_file(&self, path: &Path, metadata: &Metadata, depth: usize) -> bool {
+        self.matches_extension(path) && self.matches(path, metadata, depth)
+    }
+
+    /// Checks the predicates but not the extension list.
+    pub fn matches(&self, path: &Path, metadata: &Metadata, depth: usize) -> bool {
+        self.predicates.iter().all(|p| p.accepts_file(path, metadata, depth))
+    }
+
+    /// Whether to descend into a directory at `depth` below the scan root.
+    pub fn should_enter(&self, dir: &Path, depth: usize) -> bool {
+        self.predicates.iter().all(|p| p.enters(dir, depth))
+    }
+}
+
+fn is_hidden(path: &Path) -> bool {
+    path.file_name()
+        .is_some_and(|name| name.to_string_lossy().starts_with('.'))
+}
+
+/// Parses a size such as `512`, `64k`, `10M` or `1G` (binary multiples).
+pub fn parse_size(s: &str) -> Option<u64> {
+    let s = s.trim();
+    let (number, multiplier) = match s.char_indices().last()? {
+        (i, 'k' | 'K') => (&s[..i], 1 << 10),
+        (i, 'm' | 'M') => (&s[..i], 1 << 20),
+        (i, 'g' | 'G') => (&s[..i], 1 << 30),
+        _ => (s, 1),
+    };
+    number.trim().parse::<u64>().ok()?.checked_mul(multiplier)
+}
+
+/// Parses an RFC 3339 timestamp or a `YYYY-MM-DD` date, taken as local midnight.
+pub fn parse_time(s: &str) -> Option<SystemTime> {
+    if let Ok(time) = DateTime::parse_from_rfc3339(s) {
+        return Some(time.into());
+    }
+    let midnight = NaiveDate::parse_from_str(s, "%Y-%m-%d").ok()?.and_hms_opt(0, 0, 0)?;
+    Local.from_local_datetime(&midnight).earliest().map(SystemTime::from)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use crate::testing::TempDir;
+
+    #[test]
+    fn skip_hidden_checks_every_entry_component() {
+        let filter = FileFilter::new(None).with(Predicate::SkipHidden);
+        assert!(filter.should_include(Path::new("src/main.rs")));
+        assert!(!filter.should_include(Path::new(".env")));
+        assert!(!filter.should_include(Path::new(".git/config")));
+        assert!(!filter.should_include(Path::new("project/.cache/data.txt")));
+    }
+
+    #[test]
+    fn entries_must_match_the_extension_list() {
+        let filter = FileFilter::new(Some(vec![".TXT".to_string()]));
+        assert!(filter.should_include(Path::new("docs/readme.txt")));
+        assert!(!filter.should_include(Path::new("docs/readme.md")));
+        assert!(!filter.should_include(Path::new("docs/README")));
+    }
+
+    #[test]
+    fn prunes_directories_below_the_depth_limit() {
+        let filter = FileFilter::new(None).with(Predicate::MaxDepth(2));
+        // Files directly in the root are at depth 1, so one level of
+        // directories is entered and nothing below it
+        assert!(filter.should_enter(Path::new("root/sub"), 1));
+        assert!(!filter.should_enter(Path::new("root/sub/deeper"), 2));
+
+        let hidden = FileFilter::new(None).with(Predicate::SkipHidden);
+        assert!(!hidden.should_enter(Path::new("root/.git"), 1));
+        assert!(hidden.should_enter(Path::new("root/src"), 1));
+    }
+
+    #[test]
+    fn checks_depth_size_and_modification_time() {
+        let dir = TempDir::new();
+        let path = dir.write("a.txt", [b'x'; 100]);
+        let modified = parse_time("2024-06-01T12:00:00Z").unwrap();
+        filetime::set_file_mtime(&path, filetime::FileTime::from_system_time(modified)).unwrap();
+        let metadata = fs::metadata(&path).unwrap();
+        let accepts = |predicate, depth| FileFilter::new(None).with(predicate).matches(&path, &metadata, depth);
+
+        assert!(accepts(Predicate::MaxDepth(2), 2));
+        assert!(!accepts(Predicate::MaxDepth(2), 3));
+        assert!(accepts(Predicate::MinSize(100), 1));
+        assert!(!accepts(Predicate::MinSize(101), 1));
+        assert!(accepts(Predicate::MaxSize(100), 1));
+        assert!(!accepts(Predicate::MaxSize(99), 1));
+        assert!(accepts(Predicate::ModifiedSince(modified), 1));
+        assert!(!accepts(Predicate::ModifiedSince(parse_time("2024-06-02").unwrap()), 1));
+        assert!(accepts(Predicate::ModifiedBefore(parse_time("2024-06-02").unwrap()), 1));
+        assert!(!accepts(Predicate::ModifiedBefore(modified), 1));
+    }
+
+    #[test]
+    fn parses_sizes() {
+        assert_eq!(parse_size("512"), Some(512));
+        assert_eq!(parse_size("64k"), Some(64 * 1024));
+        assert_eq!(parse_size(" 10M "), Some(10 * 1024 * 1024));
+        assert_eq!(parse_size("1G"), Some(1 << 30));
+        assert_eq!(parse_size("1.5M"), None);
+        assert_eq!(parse_size("M"), None);
+        assert_eq!(parse_size("99999999999G"), None);
+    }
+
+    #[test]
+    fn parses_times() {
+        let utc = parse_time("2024-06-01T12:00:00+02:00").unwrap();
+        assert_eq!(utc, SystemTime::UNIX_EPOCH + std::time::Duration::from_secs(1_717_236_000));
+        let midnight = Local.with_ymd_and_hms(2024, 6, 1, 0, 0, 0).unwrap();
+        assert_eq!(parse_time("2024-06-01"), Some(midnight.into()));
+        assert_eq!(parse_time("01.06.2024"), None);
+        assert_eq!(parse_time("2024-13-01"), None);
+    }
 }
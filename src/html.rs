@@ -0,0 +1,296 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Write;
+use std::fs;
+use std::path::{Path, PathBuf};
+use crate::conversion::{ConversionResult, ConversionStatus};
+use crate::filenames::{escaped_path, path_uri};
+use crate::output::{escape_markup as escape, ScanReport};
+
+const STYLE: &str = r#"
+body { font-family: system-ui, sans-serif; margin: 2em; color: #222; }
+h1 { font-size: 1.5em; } h2 { font-size: 1.2em; margin-top: 2em; }
+table { border-collapse: collapse; margin-top: .5em; }
+th, td { border: 1px solid #ccc; padding: .25em .6em; text-align: left; vertical-align: top; }
+th { background: #f0f0f0; }
+table.sortable th { cursor: pointer; user-select: none; }
+td.num { text-align: right; }
+.bar { background: #4a7ab5; height: .8em; display: inline-block; }
+.error { color: #b00020; } .warning { color: #a05a00; } .info { color: #555; }
+input#filter { padding: .3em; width: 30em; }
+"#;
+
+const SCRIPT: &str = r#"
+document.querySelectorAll('table.sortable').forEach(function (table) {
+  table.querySelectorAll('th').forEach(function (th, column) {
+    th.addEventListener('click', function () {
+      var body = table.tBodies[0];
+      var ascending = th.dataset.order !== 'asc';
+      th.dataset.order = ascending ? 'asc' : 'desc';
+      var key = function (row) {
+        var cell = row.cells[column];
+        return cell.dataset.sort !== undefined ? parseFloat(cell.dataset.sort) : cell.textContent.toLowerCase();
+      };
+      Array.from(body.rows)
+        .sort(function (a, b) {
+          var x = key(a), y = key(b);
+          return (x < y ? -1 : x > y ? 1 : 0) * (ascending ? 1 : -1);
+        })
+        .forEach(function (row) { body.appendChild(row); });
+    });
+  });
+});
+var filter = document.getElementById('filter');
+if (filter) {
+  filter.addEventListener('input', function () {
+    var needle = filter.value.toLowerCase();
+    Array.from(document.getElementById('files').tBodies[0].rows).forEach(function (row) {
+      row.style.display = row.textContent.toLowerCase().indexOf(needle) === -1 ? 'none' : '';
+    });
+  });
+}
+"#;
+
+/// File URL for a log or backup, so the report links to it from anywhere.
+fn file_url(path: &Path) -> String {
+    let absolute = fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+    format!("file://{}", path_uri(&absolute))
+}
+
+/// Splits the conversion log into the lines written for each file, from its
+/// "Processing file:" line up to the next file or archive, keyed by the path
+/// as logged.
+fn log_sections(log: &str) -> Vec<(&str, Vec<&str>)> {
+    let mut sections: Vec<(&str, Vec<&str>)> = Vec::new();
+    let mut in_file = false;
+    for line in log.lines() {
+        if let Some((_, path)) = line.split_once("] Processing file: ") {
+            sections.push((path, vec![line]));
+            in_file = true;
+        } else if line.contains("] Processing archive: ") {
+            in_file = false;
+        } else if let (true, Some((_, lines))) = (in_file, sections.last_mut()) {
+            lines.push(line);
+        }
+    }
+    sections
+}
+
+/// Renders the scan as a single self-contained HTML page: encoding
+/// distribution, per-directory breakdown, a sortable and filterable file
+/// table, and the conversion results with links to the files in `links`,
+/// such as the conversion log and the backup directory. Each conversion
+/// result links to its own entries from `log`, which are embedded.
+pub fn render_html(
+    report: &ScanReport,
+    conversions: &[ConversionResult],
+    links: &[(&str, PathBuf)],
+    log: &str,
+) -> Result<String, std::fmt::Error> {
+    let by_path: HashMap<&Path, &ConversionResult> = conversions.iter()
+        .map(|result| (result.path.as_path(), result))
+        .collect();
+    let sections = log_sections(log);
+    let log_anchors: HashMap<&str, usize> = sections.iter().enumerate()
+        .map(|(index, (path, _))| (*path, index))
+        .collect();
+    // Status of a conversion, linked to its log entries when there are any
+    let status_link = |result: &ConversionResult| {
+        match log_anchors.get(result.path.display().to_string().as_str()) {
+            Some(index) => format!("<a href=\"#log-{}\">{}</a>", index, result.status.as_str()),
+            None => result.status.as_str().to_string(),
+        }
+    };
+
+    let mut html = String::new();
+    writeln!(html, "<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">")?;
+    writeln!(html, "<title>Encoding report</title>\n<style>{}</style>\n</head>\n<body>", STYLE)?;
+    writeln!(html, "<h1>Encoding report</h1>")?;
+    write!(html, "<p>{} file(s) scanned", report.total_files)?;
+    if report.deselected_files > 0 {
+        write!(html, ", {} not matching the selectors", report.deselected_files)?;
+    }
+    if !report.errors.is_empty() {
+        write!(html, ", {} scan error(s)", report.errors.len())?;
+    }
+    writeln!(html, ".</p>")?;
+
+    writeln!(html, "<h2>Encoding distribution</h2>")?;
+    writeln!(html, "<table class=\"sortable\"><thead><tr><th>Encoding</th><th>BOM</th><th>Files</th><th>Share</th></tr></thead><tbody>")?;
+    for stat in &report.encoding_stats {
+        writeln!(html,
+            "<tr><td>{}</td><td>{}</td><td class=\"num\" data-sort=\"{}\">{}</td><td data-sort=\"{:.3}\"><span class=\"bar\" style=\"width: {:.0}px\"></span> {:.1}%</td></tr>",
+            escape(&stat.encoding),
+            stat.bom.unwrap_or("No BOM"),
+            stat.count, stat.count,
+            stat.percentage, stat.percentage * 2.0, stat.percentage,
+        )?;
+    }
+    writeln!(html, "</tbody></table>")?;
+
+    // Directory of each file as scanned, with its encodings and findings
+    let mut directories: BTreeMap<String, (usize, BTreeMap<&str, usize>, usize)> = BTreeMap::new();
+    for file in &report.files {
+        let scanned = file.root.as_deref().map_or(file.path.clone(), |root| root.join(&file.path));
+        let directory = scanned.parent().map_or(String::new(), escaped_path);
+        let entry = directories.entry(directory).or_default();
+        entry.0 += 1;
+        *entry.1.entry(file.encoding.encoding.as_str()).or_insert(0) += 1;
+        entry.2 += file.findings.len();
+    }
+    writeln!(html, "<h2>Directories</h2>")?;
+    writeln!(html, "<table class=\"sortable\"><thead><tr><th>Directory</th><th>Files</th><th>Encodings</th><th>Findings</th></tr></thead><tbody>")?;
+    for (directory, (files, encodings, findings)) in &directories {
+        let encodings: Vec<String> = encodings.iter()
+            .map(|(encoding, count)| format!("{}: {}", escape(encoding), count))
+            .collect();
+        writeln!(html,
+            "<tr><td>{}</td><td class=\"num\" data-sort=\"{}\">{}</td><td>{}</td><td class=\"num\" data-sort=\"{}\">{}</td></tr>",
+            if directory.is_empty() { ".".to_string() } else { escape(directory) },
+            files, files, encodings.join(", "), findings, findings,
+        )?;
+    }
+    writeln!(html, "</tbody></table>")?;
+
+    writeln!(html, "<h2>Files</h2>")?;
+    writeln!(html, "<input id=\"filter\" type=\"search\" placeholder=\"Filter files\">")?;
+    write!(html, "<table id=\"files\" class=\"sortable\"><thead><tr><th>Path</th><th>Encoding</th><th>BOM</th>")?;
+    write!(html, "<th>Line endings</th><th>Confidence</th><th>Findings</th>")?;
+    if !conversions.is_empty() {
+        write!(html, "<th>Conversion</th>")?;
+    }
+    writeln!(html, "</tr></thead><tbody>")?;
+    for file in &report.files {
+        let scanned = file.root.as_deref().map_or(file.path.clone(), |root| root.join(&file.path));
+        let mut findings = String::new();
+        if let Some(error) = &file.error {
+            write!(findings, "<div class=\"error\">{}: {}</div>", error.kind.as_str(), escape(&error.message))?;
+        }
+        for finding in &file.findings {
+            let location = match (finding.line, finding.column) {
+                (Some(line), Some(column)) => format!(" at {}:{}", line, column),
+                _ => String::new(),
+            };
+            write!(findings, "<div class=\"{}\">{}{}: {}</div>",
+                finding.severity.as_str(), escape(finding.kind), location, escape(&finding.message))?;
+        }
+        write!(html,
+            "<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td class=\"num\" data-sort=\"{}\">{:.0}%</td><td data-sort=\"{}\">{}</td>",
+            escape(&escaped_path(&scanned)),
+            escape(&file.encoding.encoding),
+            file.encoding.bom.unwrap_or(""),
+            file.line_endings.map_or("", |l| l.as_str()),
+            file.confidence, file.confidence * 100.0,
+            file.findings.len() + usize::from(file.error.is_some()), findings,
+        )?;
+        if !conversions.is_empty() {
+            let status = by_path.get(scanned.as_path()).map_or(String::new(), |r| status_link(r));
+            write!(html, "<td>{}</td>", status)?;
+        }
+        writeln!(html, "</tr>")?;
+    }
+    writeln!(html, "</tbody></table>")?;
+
+    if !conversions.is_empty() || !links.is_empty() {
+        writeln!(html, "<h2>Conversion</h2>")?;
+        if !links.is_empty() {
+            let items: Vec<String> = links.iter()
+                .map(|(label, path)| format!("<a href=\"{}\">{}</a>", escape(&file_url(path)), escape(label)))
+                .collect();
+            writeln!(html, "<p>{}</p>", items.join(" · "))?;
+        }
+        let counts: Vec<String> = [
+            ConversionStatus::Converted,
+            ConversionStatus::AlreadyCompliant,
+            ConversionStatus::Skipped,
+            ConversionStatus::Failed,
+        ].iter()
+            .map(|&status| format!("{}: {}", status.as_str(), conversions.iter().filter(|r| r.status == status).count()))
+            .collect();
+        writeln!(html, "<p>{}</p>", counts.join(", "))?;
+        writeln!(html, "<table class=\"sortable\"><thead><tr><th>Path</th><th>Status</th><th>Message</th></tr></thead><tbody>")?;
+        for result in conversions.iter().filter(|r| r.error.is_some()) {
+            let error = result.error.as_ref().map_or(String::new(), |e| format!("{}: {}", e.kind.as_str(), e.message));
+            writeln!(html, "<tr><td>{}</td><td>{}</td><td>{}</td></tr>",
+                escape(&escaped_path(&result.path)), status_link(result), escape(&error))?;
+        }
+        writeln!(html, "</tbody></table>")?;
+    }
+
+    if !sections.is_empty() {
+        writeln!(html, "<h2>Conversion log</h2>")?;
+        for (index, (path, lines)) in sections.iter().enumerate() {
+            writeln!(html, "<h3 id=\"log-{}\">{}</h3>", index, escape(path))?;
+            writeln!(html, "<pre>{}</pre>", lines.iter().map(|line| escape(line)).collect::<Vec<_>>().join("\n"))?;
+        }
+    }
+
+    if !report.errors.is_empty() {
+        writeln!(html, "<h2>Scan errors</h2>")?;
+        writeln!(html, "<table class=\"sortable\"><thead><tr><th>Path</th><th>Kind</th><th>Message</th></tr></thead><tbody>")?;
+        for error in &report.errors {
+            writeln!(html, "<tr><td>{}</td><td>{}</td><td>{}</td></tr>",
+                escape(&escaped_path(&error.path)), error.error.kind.as_str(), escape(&error.error.message))?;
+        }
+        writeln!(html, "</tbody></table>")?;
+    }
+
+    if !report.filename_issues.is_empty() {
+        writeln!(html, "<h2>Problematic file names</h2>")?;
+        writeln!(html, "<table class=\"sortable\"><thead><tr><th>Path</th><th>Reason</th><th>Likely encoding</th><th>Suggested name</th></tr></thead><tbody>")?;
+        for issue in &report.filename_issues {
+            writeln!(html, "<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>",
+                escape(&escaped_path(&issue.path)), issue.reason, escape(&issue.likely_encoding), escape(&issue.suggested_name))?;
+        }
+        writeln!(html, "</tbody></table>")?;
+    }
+
+    write!(html, "<script>{}</script>\n</body>\n</html>", SCRIPT)?;
+    Ok(html)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LOG: &str = "\
+[2026-01-01][10:00:00][INFO] Starting batch conversion of 2 files
+[2026-01-01][10:00:00][INFO] Processing file: src/a.txt
+[2026-01-01][10:00:00][INFO] ✓ Successfully converted src/a.txt to UTF-8 with Unix line endings
+[2026-01-01][10:00:00][INFO] Processing file: src/<b>.txt
+[2026-01-01][10:00:00][ERROR] ✗ Failed to convert src/<b>.txt: Encoding error
+[2026-01-01][10:00:00][INFO] Processing archive: src/c.zip
+[2026-01-01][10:00:00][INFO] ✓ Converted 1 entries of src/c.zip";
+
+    #[test]
+    fn splits_the_log_by_file() {
+        let sections = log_sections(LOG);
+        assert_eq!(sections.len(), 2);
+        assert_eq!(sections[0].0, "src/a.txt");
+        assert_eq!(sections[0].1.len(), 2);
+        assert_eq!(sections[1].0, "src/<b>.txt");
+        assert!(sections[1].1.iter().all(|line| !line.contains("c.zip")));
+    }
+
+    #[test]
+    fn links_conversion_results_to_their_log_entries() {
+        let report = ScanReport {
+            total_files: 0,
+            deselected_files: 0,
+            files: Vec::new(),
+            encoding_stats: Vec::new(),
+            filename_issues: Vec::new(),
+            errors: Vec::new(),
+        };
+        let conversions = [
+            ConversionResult::with_error(
+                Path::new("src/<b>.txt"),
+                ConversionStatus::Failed,
+                crate::access::FileError::new(crate::access::ErrorKind::Encoding, "Encoding error".to_string()),
+            ),
+        ];
+        let html = render_html(&report, &conversions, &[], LOG).unwrap();
+        assert!(html.contains("<a href=\"#log-1\">failed</a>"));
+        assert!(html.contains("<h3 id=\"log-1\">src/&lt;b&gt;.txt</h3>"));
+        assert!(!html.contains("<b>"));
+    }
+}
@@ -0,0 +1,149 @@
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::path::{Path, PathBuf};
+use crate::conversion::{ConversionResult, ConversionStatus};
+use crate::filenames::escaped_path;
+use crate::findings::Severity;
+use crate::output::{escape_markup as escape, ScanReport};
+
+/// One `<testcase>`: a scanned file and what went wrong with it.
+struct TestCase {
+    classname: String,
+    name: String,
+    failures: Vec<(String, String)>,
+    errors: Vec<(String, String)>,
+    skipped: Option<String>,
+}
+
+impl TestCase {
+    fn new(root: Option<&Path>, path: &Path) -> Self {
+        TestCase {
+            classname: root.map_or(".".to_string(), escaped_path),
+            name: escaped_path(path),
+            failures: Vec::new(),
+            errors: Vec::new(),
+            skipped: None,
+        }
+    }
+}
+
+/// Renders the scan as a JUnit XML test suite with one test case per file.
+/// Error-level findings, such as encoding policy violations, and failed
+/// conversions become failures; unreadable files become errors, and files
+/// the conversion skipped are marked skipped when nothing else is wrong.
+pub fn render_junit(report: &ScanReport, conversions: &[ConversionResult]) -> Result<String, std::fmt::Error> {
+    let conversions: HashMap<&Path, &ConversionResult> = conversions.iter()
+        .map(|result| (result.path.as_path(), result))
+        .collect();
+
+    let mut cases = Vec::new();
+    for file in &report.files {
+        let mut case = TestCase::new(file.root.as_deref(), &file.path);
+        if let Some(error) = &file.error {
+            case.errors.push((error.kind.as_str().to_string(), error.message.clone()));
+        }
+        for finding in file.findings.iter().filter(|f| f.severity == Severity::Error) {
+            let message = match (finding.line, finding.column) {
+                (Some(line), Some(column)) => format!("{} at {}:{}", finding.message, line, column),
+                _ => finding.message.clone(),
+            };
+            case.failures.push((finding.kind.to_string(), message));
+        }
+        // Conversion results carry the path as scanned
+        let scanned: PathBuf = file.root.as_deref().map_or(file.path.clone(), |root| root.join(&file.path));
+        if let Some(result) = conversions.get(scanned.as_path()) {
+            let message = result.error.as_ref().map(|e| (e.kind.as_str().to_string(), e.message.clone()));
+            match result.status {
+                ConversionStatus::Failed => case.failures.push(
+                    message.unwrap_or_else(|| ("conversion-failed".to_string(), "Conversion failed".to_string()))
+                ),
+                ConversionStatus::Skipped => case.skipped = Some(message.map_or(String::new(), |(_, m)| m)),
+                _ => {}
+            }
+        }
+        cases.push(case);
+    }
+    for error in &report.errors {
+        let mut case = TestCase::new(None, &error.path);
+        case.errors.push((error.error.kind.as_str().to_string(), error.error.message.clone()));
+        cases.push(case);
+    }
+
+    let failures = cases.iter().filter(|c| !c.failures.is_empty()).count();
+    let errors = cases.iter().filter(|c| c.failures.is_empty() && !c.errors.is_empty()).count();
+    let skipped = cases.iter()
+        .filter(|c| c.failures.is_empty() && c.errors.is_empty() && c.skipped.is_some())
+        .count();
+
+    let mut xml = String::new();
+    writeln!(xml, r#"<?xml version="1.0" encoding="UTF-8"?>"#)?;
+    writeln!(xml, r#"<testsuites name="{}" tests="{}" failures="{}" errors="{}" skipped="{}">"#,
+        env!("CARGO_PKG_NAME"), cases.len(), failures, errors, skipped)?;
+    writeln!(xml, r#"  <testsuite name="encoding" tests="{}" failures="{}" errors="{}" skipped="{}">"#,
+        cases.len(), failures, errors, skipped)?;
+    for case in &cases {
+        write!(xml, r#"    <testcase classname="{}" name="{}""#, escape(&case.classname), escape(&case.name))?;
+        if case.failures.is_empty() && case.errors.is_empty() && case.skipped.is_none() {
+            writeln!(xml, "/>")?;
+            continue;
+        }
+        writeln!(xml, ">")?;
+        for (kind, message) in &case.failures {
+            writeln!(xml, r#"      <failure type="{}" message="{}"/>"#, escape(kind), escape(message))?;
+        }
+        for (kind, message) in &case.errors {
+            writeln!(xml, r#"      <error type="{}" message="{}"/>"#, escape(kind), escape(message))?;
+        }
+        if let (Some(message), true) = (&case.skipped, case.failures.is_empty() && case.errors.is_empty()) {
+            writeln!(xml, r#"      <skipped message="{}"/>"#, escape(message))?;
+        }
+        writeln!(xml, "    </testcase>")?;
+    }
+    writeln!(xml, "  </testsuite>")?;
+    write!(xml, "</testsuites>")?;
+    Ok(xml)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::access::{ErrorKind, FileError};
+    use crate::statistics::Statistics;
+    use crate::testing::{analyze, TempDir};
+
+    #[test]
+    fn maps_findings_conversions_and_scan_errors_to_testcases() {
+        let dir = TempDir::new();
+        let root = dir.path().join("in");
+        let mut stats = Statistics::new();
+        stats.set_root(Some(root.clone()));
+        for (name, content) in [
+            ("ok.txt", "fine\n"),
+            ("bad.txt", "fine\n"),
+            ("ro.txt", "fine\n"),
+            ("bidi.rs", "// \u{202E}\n"),
+        ] {
+            stats.add_file(analyze(&dir.write(format!("in/{}", name), content)));
+        }
+        stats.add_scan_error(&root.join("secret"), FileError::new(ErrorKind::PermissionDenied, "denied".to_string()));
+        let conversions = [
+            ConversionResult::new(&root.join("ok.txt"), ConversionStatus::Converted),
+            ConversionResult::with_error(&root.join("bad.txt"), ConversionStatus::Failed,
+                FileError::new(ErrorKind::Encoding, "cannot encode".to_string())),
+            ConversionResult::with_error(&root.join("ro.txt"), ConversionStatus::Skipped,
+                FileError::new(ErrorKind::ReadOnlyTarget, "ro.txt is read-only".to_string())),
+            // A file with failing findings is not reported as skipped
+            ConversionResult::new(&root.join("bidi.rs"), ConversionStatus::Skipped),
+        ];
+
+        let xml = render_junit(&stats.generate_report(), &conversions).unwrap();
+        assert!(xml.contains(r#"<testsuite name="encoding" tests="5" failures="2" errors="1" skipped="1">"#), "{}", xml);
+        let classname = escaped_path(&root);
+        assert!(xml.contains(&format!(r#"<testcase classname="{}" name="ok.txt"/>"#, classname)), "{}", xml);
+        assert!(xml.contains(r#"<failure type="encoding" message="cannot encode"/>"#), "{}", xml);
+        assert!(xml.contains(r#"<skipped message="ro.txt is read-only"/>"#), "{}", xml);
+        assert!(xml.contains(r#"<failure type="bidi-control" message="bidirectional override/isolate U+202E at 1:4"/>"#), "{}", xml);
+        assert!(!xml.contains(r#"<skipped message=""/>"#), "{}", xml);
+        assert!(xml.contains(r#"<error type="permission-denied" message="denied"/>"#), "{}", xml);
+    }
+}
@@ -1,35 +1,74 @@
+mod access;
 mod detection;
+mod findings;
+mod mojibake;
+mod properties;
+mod normalization;
+mod security;
+mod filenames;
+mod archive;
+mod compression;
+mod config;
+mod analysis;
+mod scanner;
+mod selection;
 mod statistics;
 mod filter;
 mod output;
 mod conversion;
+mod metadata;
+mod plan;
 mod safety;
+mod sarif;
+mod junit;
+mod html;
+#[cfg(test)]
+mod testing;
 
 use clap::Parser;
+use std::collections::HashMap;
 use std::fs;
+use std::io::{self, Read, Write};
 use std::path::{Path, PathBuf};
 use std::error::Error;
-use log::{info, error};
-use detection::detect_encoding;
+use log::{info, error, warn};
+use analysis::AnalysisOptions;
+use detection::{declared_encoding_name, detect_encoding_from_bytes, FileEncoding};
+use selection::{Selection, Selector};
 use statistics::Statistics;
-use filter::FileFilter;
-use output::OutputFormat;
-use conversion::{EncodingConverter, LineEnding};
+use filter::{FileFilter, Predicate};
+use output::{FileReport, OutputFormat};
+use conversion::{ConversionError, ConversionOptions, ConversionResult, ConversionStatus, EncodingConverter, LineEnding};
+use normalization::NormalizationForm;
+use filenames::{escaped_path, FilenameIssue};
+use archive::ArchiveKind;
+use metadata::{file_id, PreserveOptions};
 use safety::ConversionSafety;
+use scanner::{ScanOptions, Scanner};
+use access::{ErrorKind, FileError, ReadOnlyPolicy};
+use config::{Config, SourceOverrides};
 
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
 struct Args {
-    /// Path to process
-    #[arg(short, long)]
-    path: String,
+    /// Paths to process; files found under each are reported relative to it
+    #[arg(short, long, num_args = 1.., required_unless_present_any = ["filter", "files_from"])]
+    path: Vec<String>,
+
+    /// Read further paths to process from a file ("-" for stdin), one per
+    /// line or NUL-separated as printed by `find -print0`
+    #[arg(long, value_name = "FILE", conflicts_with = "filter")]
+    files_from: Option<String>,
 
     /// File extensions to include (e.g., "txt,md,rs")
     /// If not specified, all files will be included
     #[arg(short, long, value_delimiter = ',')]
     extensions: Option<Vec<String>>,
 
-    /// Output format (text, json, or csv)
+    /// Output format (text, json, ndjson, csv, sarif, junit, or html). ndjson
+    /// writes one record per file as it is scanned, then a summary; junit
+    /// and html are written after conversion so they include its results.
+    /// sarif always includes the --security checks
     #[arg(short, long, default_value = "text")]
     format: String,
 
@@ -53,161 +92,1062 @@ struct Args {
     /// Skip verification of converted files
     #[arg(short = 's', long)]
     skip_verification: bool,
+
+    /// Decode each section of mixed-encoding files with its own encoding
+    /// when converting, instead of using the single detected encoding
+    #[arg(long)]
+    repair_mixed: bool,
+
+    /// Reverse double-encoded UTF-8 ("Ã¤" instead of "ä") when converting,
+    /// up to the given number of layers
+    #[arg(long, num_args = 0..=1, default_missing_value = "3", value_name = "LAYERS")]
+    repair_mojibake: Option<usize>,
+
+    /// Only repair double encoding detected with at least this confidence
+    /// (e.g. 0.6 or 60%)
+    #[arg(long, default_value = "0.6", value_name = "CONFIDENCE")]
+    mojibake_confidence: String,
+
+    /// Trust in-file encoding declarations (XML prolog, meta charset, coding
+    /// cookies, modelines, @charset) over the detected encoding
+    #[arg(long)]
+    trust_declarations: bool,
+
+    /// Leave in-file charset declarations untouched when converting
+    #[arg(long)]
+    keep_declarations: bool,
+
+    /// Translate \uXXXX escapes in Java .properties files: unescape them when
+    /// converting to Unicode, escape non-Latin-1 characters for ISO-8859-1
+    #[arg(long)]
+    properties: bool,
+
+    /// Unicode normalization to apply when converting (nfc, nfd, nfkc, nfkd)
+    #[arg(long)]
+    normalize: Option<String>,
+
+    /// Remove byte order marks found in the middle of files when converting
+    #[arg(long)]
+    strip_embedded_boms: bool,
+
+    /// Scan for Trojan-Source bidirectional controls, zero-width and control
+    /// characters, stray BOMs and confusable identifiers
+    #[arg(long)]
+    security: bool,
+
+    /// Exit with status 3 if the security scan reports anything (implies --security)
+    #[arg(long)]
+    check: bool,
+
+    /// Rename files and directories whose names are not valid UTF-8
+    #[arg(long)]
+    rename_filenames: bool,
+
+    /// Also report and rename names that are not in Unicode NFC
+    #[arg(long)]
+    filename_nfc: bool,
+
+    /// Scan files inside .zip, .tar, .tar.gz and .tgz archives
+    #[arg(long)]
+    scan_archives: bool,
+
+    /// Write converted copies of scanned archives to the output directory
+    /// (implies --scan-archives)
+    #[arg(long, requires = "convert_to")]
+    convert_archives: bool,
+
+    /// Treat gzip, xz and zstd files as opaque instead of analyzing and
+    /// converting their decompressed content
+    #[arg(long)]
+    no_decompress: bool,
+
+    /// Read from stdin and write the converted text to stdout instead of
+    /// scanning a path; diagnostics go to stderr
+    #[arg(long, requires = "convert_to", conflicts_with = "path")]
+    filter: bool,
+
+    /// Source encoding to assume for every file (or the --filter input)
+    /// instead of the detected one
+    #[arg(long, value_name = "ENCODING")]
+    from: Option<String>,
+
+    /// Do not copy permission bits to converted files and backups. Copies of
+    /// read-only files are read-only too unless this is given; a later run
+    /// then handles them by the --read-only policy
+    #[arg(long)]
+    no_preserve_mode: bool,
+
+    /// Do not copy access and modification times to converted files and backups
+    #[arg(long)]
+    no_preserve_times: bool,
+
+    /// Do not copy owner and group to converted files and backups
+    #[arg(long)]
+    no_preserve_ownership: bool,
+
+    /// Do not copy extended attributes to converted files and backups
+    #[arg(long)]
+    no_preserve_xattrs: bool,
+
+    /// Only descend this many directory levels (1 = files in the path itself)
+    #[arg(long, value_name = "DEPTH")]
+    max_depth: Option<usize>,
+
+    /// Skip files and directories whose name starts with a dot
+    #[arg(long)]
+    skip_hidden: bool,
+
+    /// Skip files smaller than this (e.g. 512, 10k, 1M)
+    #[arg(long, value_name = "SIZE")]
+    min_size: Option<String>,
+
+    /// Skip files larger than this (e.g. 512, 10k, 1M)
+    #[arg(long, value_name = "SIZE")]
+    max_size: Option<String>,
+
+    /// Only include files modified at or after this date (YYYY-MM-DD or RFC 3339)
+    #[arg(long, value_name = "DATE")]
+    modified_since: Option<String>,
+
+    /// Only include files modified before this date (YYYY-MM-DD or RFC 3339)
+    #[arg(long, value_name = "DATE")]
+    modified_before: Option<String>,
+
+    /// Report files not detected as one of these encodings as violating
+    /// the encoding policy (pure ASCII satisfies UTF-8)
+    #[arg(long, value_delimiter = ',', value_name = "ENCODINGS")]
+    expect_encoding: Option<Vec<String>>,
+
+    /// Only report and convert files detected as one of these encodings
+    /// (names as shown in the report, e.g. "windows-1252,ISO-8859-1")
+    #[arg(long, value_delimiter = ',', value_name = "ENCODINGS")]
+    select_encoding: Option<Vec<String>>,
+
+    /// Only report and convert files with (yes) or without (no) a BOM
+    #[arg(long, value_name = "yes|no")]
+    select_bom: Option<String>,
+
+    /// Only report and convert files with these line endings (lf, crlf, cr, mixed, none)
+    #[arg(long, value_delimiter = ',', value_name = "STYLES")]
+    select_line_endings: Option<Vec<String>>,
+
+    /// Only report and convert files detected with at least this confidence (e.g. 0.8 or 80%)
+    #[arg(long, value_name = "CONFIDENCE")]
+    min_confidence: Option<String>,
+
+    /// Only report and convert files detected with at most this confidence (e.g. 0.5 or 50%)
+    #[arg(long, value_name = "CONFIDENCE")]
+    max_confidence: Option<String>,
+
+    /// Follow symlinked files and directories while scanning (the default)
+    #[arg(long, overrides_with = "no_follow")]
+    follow_symlinks: bool,
+
+    /// Skip symlinks while scanning
+    #[arg(long, overrides_with = "follow_symlinks")]
+    no_follow: bool,
+
+    /// Keep hard-linked files linked in the output directory instead of
+    /// writing a separate converted copy for each name
+    #[arg(long)]
+    preserve_hardlinks: bool,
+
+    /// What to do when a converted file would overwrite a read-only one:
+    /// skip it, report it as failed, or lift the flag for the conversion
+    #[arg(long, default_value = "report", value_name = "POLICY")]
+    read_only: String,
+
+    /// Show what --convert-to would do for each file without writing anything
+    #[arg(long, requires = "convert_to")]
+    dry_run: bool,
+
+    /// With --dry-run, print a unified diff of the text of files whose content changes
+    #[arg(long, requires = "dry_run")]
+    diff: bool,
+
+    /// JSON config file with per-glob source encoding overrides, e.g.
+    /// {"overrides": [{"glob": "legacy/**/*.txt", "encoding": "windows-1252"}]}
+    /// Globs match file paths, so it cannot be combined with --filter
+    #[arg(long, value_name = "FILE", conflicts_with = "filter")]
+    config: Option<String>,
+
+    /// Write the junit or html report to this file instead of stdout. While
+    /// a report other than text goes to stdout, progress messages and the
+    /// log go to stderr.
+    /// An html report of a conversion or rename run goes to the output
+    /// directory by default
+    #[arg(long, value_name = "FILE")]
+    report_file: Option<String>,
+}
+
+/// Exit status when --check finds security issues
+const EXIT_CHECK_FAILED: i32 = 3;
+/// Exit status when --filter input cannot be decoded
+const EXIT_DECODE_FAILED: i32 = 4;
+/// Exit status when --filter output cannot be encoded in the target encoding
+const EXIT_ENCODE_FAILED: i32 = 5;
+/// Exit status when --repair-mojibake would make the --filter input worse
+const EXIT_REPAIR_REJECTED: i32 = 6;
+
+/// Per-file policies for a conversion run
+struct BatchOptions {
+    skip_verification: bool,
+    read_only: ReadOnlyPolicy,
+    preserve_hardlinks: bool,
 }
 
-fn scan_directory(
-    path: &Path,
-    stats: &mut Statistics,
-    filter: &FileFilter,
-) -> Result<(), Box<dyn Error>> {
-    if path.is_dir() {
-        for entry in fs::read_dir(path)? {
-            let entry = entry?;
-            let path = entry.path();
-            scan_directory(&path, stats, filter)?;
-        }
-    } else if filter.should_include(path) {
-        let name = path.file_name()
-            .map(|n| n.to_string_lossy().to_string())
-            .unwrap_or_else(|| path.to_string_lossy().to_string());
-        let encoding = detect_encoding(path);
-        stats.add_file(path.to_path_buf(), name, encoding);
+/// Gives a second name of an already converted file its output: a hard link
+/// to the first output, or a copy of it.
+fn link_converted(path: &Path, first: &Path, output_path: &Path, preserve_hardlinks: bool) -> ConversionResult {
+    // Converting in place already changed every name of the file
+    if file_id(output_path).is_some_and(|id| file_id(first) == Some(id)) {
+        info!("✓ {} shares its content with {}", path.display(), first.display());
+        return ConversionResult::new(path, ConversionStatus::Converted);
+    }
+    let linked = if preserve_hardlinks {
+        fs::remove_file(output_path)
+            .or_else(|e| if e.kind() == io::ErrorKind::NotFound { Ok(()) } else { Err(e) })
+            .and_then(|_| fs::hard_link(first, output_path))
+            .map(|_| "Linked")
+    } else {
+        warn!("{} is another name for {}; writing a separate copy (use --preserve-hardlinks to keep them linked)",
+            path.display(), first.display());
+        fs::copy(first, output_path).map(|_| "Copied")
+    };
+    match linked {
+        Ok(action) => {
+            info!("✓ {} converted {} to {}", action, first.display(), output_path.display());
+            ConversionResult::new(path, ConversionStatus::Converted)
+        }
+        Err(e) => {
+            error!("✗ Failed to link {} to {}: {}", output_path.display(), first.display(), e);
+            ConversionResult::with_error(path, ConversionStatus::Failed, FileError::from(&e))
+        }
     }
-    Ok(())
+}
+
+/// `converted` in the directory that contains every scanned root, so the
+/// output of one root never lands inside another.
+fn default_output_dir(roots: &[PathBuf]) -> PathBuf {
+    if let [root] = roots {
+        return root.join("converted");
+    }
+    // An empty root stands for the current directory
+    let absolute = |root: &PathBuf| {
+        let root = if root.as_os_str().is_empty() { Path::new(".") } else { root.as_path() };
+        std::path::absolute(root).unwrap_or_else(|_| root.to_path_buf())
+    };
+    let mut common = roots.first().map(absolute).unwrap_or_default();
+    for root in roots.iter().skip(1).map(absolute) {
+        while !root.starts_with(&common) && common.pop() {}
+    }
+    common.join("converted")
 }
 
 fn convert_files(
-    files: &[(PathBuf, String, detection::FileEncoding)],
+    files: &[FileReport],
     target_encoding: &str,
-    line_ending: LineEnding,
+    options: &ConversionOptions,
     output_dir: &Path,
     safety: &ConversionSafety,
-    skip_verification: bool,
-) -> Result<(), Box<dyn Error>> {
+    batch: &BatchOptions,
+) -> Result<Vec<ConversionResult>, Box<dyn Error>> {
     fs::create_dir_all(output_dir)?;
     info!("Starting batch conversion of {} files", files.len());
+    let mut results = Vec::new();
+    // Output path of each converted file, by device and inode
+    let mut converted: HashMap<(u64, u64), PathBuf> = HashMap::new();
 
-    for (path, name, encoding) in files {
+    // Archive entries are converted by writing a new archive, and files
+    // that could not be read have nothing to convert
+    for file in files.iter().filter(|f| f.archive.is_none() && f.error.is_none()) {
+        let path = &file.path;
         info!("Processing file: {}", path.display());
         
-        // Create backup if enabled
-        let backup_path = safety.create_backup(path)?;
-        let output_path = output_dir.join(name);
+        let output_path = output_dir.join(&file.name);
+        let options = ConversionOptions {
+            properties: options.properties && properties::is_properties_file(path),
+            ..*options
+        };
 
-        match EncodingConverter::convert_file(path, &output_path, encoding, target_encoding, line_ending) {
-            Ok(_) => {
-                // Verify conversion unless skipped
-                if !skip_verification {
-                    if let Err(e) = safety.verify_conversion(path, &output_path) {
-                        error!("Verification failed for {}: {}", path.display(), e);
-                        if let Some(backup) = backup_path {
-                            info!("Attempting rollback...");
-                            safety.rollback(path, &backup)?;
-                        }
+        if safety.is_link_outside_input(&output_path) {
+            info!("- Skipping {}: symlink target is outside the scanned directories", output_path.display());
+            results.push(ConversionResult::with_error(path, ConversionStatus::Skipped, FileError::new(
+                ErrorKind::OutsideRoot,
+                format!("{} links outside the scanned directories", output_path.display()),
+            )));
+            continue;
+        }
+
+        // Another name for a file that was already converted must not be
+        // converted again from its stale analysis
+        let id = file_id(path);
+        if let Some(first) = id.and_then(|id| converted.get(&id)) {
+            results.push(link_converted(path, first, &output_path, batch.preserve_hardlinks));
+            continue;
+        }
+
+        // Nothing has been written yet, so failures here need no rollback
+        // A compliant file is left alone in place but still belongs in a
+        // separate output directory, so it is copied there unchanged
+        let output = match EncodingConverter::prepare_file(path, &file.encoding, target_encoding, &options) {
+            Ok(Some(output)) => Some(output),
+            Ok(None) if id.is_some() && file_id(&output_path) == id => {
+                info!("= {} is already compliant with {}, skipping", path.display(), target_encoding);
+                results.push(ConversionResult::new(path, ConversionStatus::AlreadyCompliant));
+                continue;
+            }
+            Ok(None) => None,
+            Err(e) => {
+                error!("✗ Failed to convert {}: {:?}", path.display(), e);
+                results.push(ConversionResult::with_error(path, ConversionStatus::Failed, FileError::from(&e)));
+                continue;
+            }
+        };
+
+        let mut lifted = None;
+        if access::is_read_only(&output_path) {
+            let error = FileError::new(
+                ErrorKind::ReadOnlyTarget,
+                format!("{} is read-only", output_path.display()),
+            );
+            match batch.read_only {
+                ReadOnlyPolicy::Skip => {
+                    info!("- Skipping read-only {}", output_path.display());
+                    results.push(ConversionResult::with_error(path, ConversionStatus::Skipped, error));
+                    continue;
+                }
+                ReadOnlyPolicy::Report => {
+                    error!("✗ Not converting {}: {}", path.display(), error.message);
+                    results.push(ConversionResult::with_error(path, ConversionStatus::Failed, error));
+                    continue;
+                }
+                ReadOnlyPolicy::Lift => match access::lift_read_only(&output_path) {
+                    Ok(permissions) => lifted = Some(permissions),
+                    Err(e) => {
+                        error!("✗ Could not make {} writable: {}", output_path.display(), e);
+                        results.push(ConversionResult::with_error(path, ConversionStatus::Failed, FileError::from(&e)));
                         continue;
                     }
+                },
+            }
+        }
+
+        let Some(output) = output else {
+            let copied = fs::copy(path, &output_path).map(|_| safety.preserve_metadata(path, &output_path));
+            if let Some(permissions) = lifted {
+                if let Err(e) = fs::set_permissions(&output_path, permissions) {
+                    warn!("Could not restore read-only mode of {}: {}", output_path.display(), e);
+                }
+            }
+            match copied {
+                Ok(_) => {
+                    info!("= {} is already compliant with {}, copied to {}",
+                        path.display(), target_encoding, output_path.display());
+                    results.push(ConversionResult::new(path, ConversionStatus::AlreadyCompliant));
+                }
+                Err(e) => {
+                    error!("✗ Failed to copy {} to {}: {}", path.display(), output_path.display(), e);
+                    results.push(ConversionResult::with_error(path, ConversionStatus::Failed, FileError::from(&e)));
                 }
+            }
+            continue;
+        };
+
+        // Create backup if enabled
+        let backup_path = match safety.create_backup(path) {
+            Ok(backup_path) => backup_path,
+            Err(e) => {
+                error!("✗ Not converting {}: {}", path.display(), e);
+                results.push(ConversionResult::with_error(path, ConversionStatus::Failed, FileError::from(&e)));
+                continue;
+            }
+        };
 
+        let written = EncodingConverter::write_file(&output_path, &output)
+            .map_err(|e| FileError::from(&e))
+            .and_then(|_| {
+                safety.preserve_metadata(path, &output_path);
+                // Verify conversion unless skipped
+                if batch.skip_verification {
+                    return Ok(());
+                }
+                safety.verify_conversion(path, &output_path).map_err(|e| FileError::from(&e))
+            });
+
+        match written {
+            Ok(_) => {
                 info!("✓ Successfully converted {} to {} with {} line endings", 
                     path.display(), 
                     target_encoding,
-                    match line_ending {
+                    match options.line_ending {
                         LineEnding::Unix => "Unix",
                         LineEnding::Windows => "Windows",
                         LineEnding::Keep => "original",
                     }
                 );
+                results.push(ConversionResult::new(path, ConversionStatus::Converted));
+                if let Some(id) = id {
+                    converted.insert(id, output_path.clone());
+                }
             }
             Err(e) => {
-                error!("✗ Failed to convert {}: {:?}", path.display(), e);
+                error!("✗ Failed to convert {}: {}", path.display(), e.message);
+                results.push(ConversionResult::with_error(path, ConversionStatus::Failed, e));
                 if let Some(backup) = backup_path {
                     info!("Attempting rollback...");
-                    safety.rollback(path, &backup)?;
+                    if let Err(e) = safety.rollback(path, &backup) {
+                        error!("✗ {}", e);
+                    }
                 }
             }
         }
+
+        // Only after a rollback, which needs to write the file too
+        if let Some(permissions) = lifted {
+            if let Err(e) = fs::set_permissions(&output_path, permissions) {
+                warn!("Could not restore read-only mode of {}: {}", output_path.display(), e);
+            }
+        }
+    }
+    Ok(results)
+}
+
+/// Writes converted copies of the archives that scanned entries came from
+/// and returns a result for each of those entries.
+fn convert_archives(
+    files: &[FileReport],
+    target_encoding: &str,
+    options: &ConversionOptions,
+    output_dir: &Path,
+    safety: &ConversionSafety,
+) -> Vec<ConversionResult> {
+    let mut archives: HashMap<&Path, Vec<&FileReport>> = HashMap::new();
+    for file in files {
+        if let Some(entry) = &file.archive {
+            archives.entry(entry.archive.as_path()).or_default().push(file);
+        }
+    }
+
+    let mut results = Vec::new();
+    for (path, entries) in archives {
+        let Some(kind) = ArchiveKind::from_path(path) else { continue };
+        let Some(name) = path.file_name() else { continue };
+        let output_path = output_dir.join(name);
+        info!("Processing archive: {}", path.display());
+        let encodings: HashMap<String, detection::FileEncoding> = entries.iter()
+            .filter_map(|file| file.archive.as_ref().map(|entry| (entry.entry.clone(), file.encoding.clone())))
+            .collect();
+        match archive::convert_archive(path, kind, &output_path, &encodings, target_encoding, options) {
+            Ok(failed) => {
+                safety.preserve_metadata(path, &output_path);
+                info!("✓ Converted {} entries of {}", encodings.len() - failed.len(), path.display());
+                for file in entries {
+                    let error = file.archive.as_ref().and_then(|entry| failed.get(&entry.entry));
+                    results.push(match error {
+                        Some(e) => ConversionResult::with_error(&file.path, ConversionStatus::Failed, FileError::from(e)),
+                        None => ConversionResult::new(&file.path, ConversionStatus::Converted),
+                    });
+                }
+            }
+            Err(e) => {
+                error!("✗ Failed to convert archive {}: {}", path.display(), e);
+                for file in entries {
+                    results.push(ConversionResult::with_error(&file.path, ConversionStatus::Failed, FileError::new(
+                        ErrorKind::Io,
+                        format!("Could not write a converted copy of {}: {}", path.display(), e),
+                    )));
+                }
+            }
+        }
+    }
+    results
+}
+
+/// Renames the files and directories with problematic names, logging the
+/// ones that fail and carrying on. Returns the number of failed renames.
+fn rename_filenames(issues: &[FilenameIssue], safety: &ConversionSafety) -> usize {
+    let mut issues: Vec<&FilenameIssue> = issues.iter().collect();
+    // Rename entries before their parent directories so their paths stay valid
+    issues.sort_by_key(|issue| std::cmp::Reverse(issue.path.components().count()));
+    info!("Starting rename of {} file names", issues.len());
+
+    let mut failed = 0;
+    for issue in issues {
+        let target = issue.suggested_path();
+        if target.exists() {
+            error!("✗ Cannot rename {}: {} already exists", escaped_path(&issue.path), target.display());
+            failed += 1;
+            continue;
+        }
+        if issue.path.is_file() {
+            if let Err(e) = safety.create_backup(&issue.path) {
+                error!("✗ Not renaming {}: {}", escaped_path(&issue.path), e);
+                failed += 1;
+                continue;
+            }
+        }
+        if let Err(e) = fs::rename(&issue.path, &target) {
+            error!("✗ Failed to rename {}: {}", escaped_path(&issue.path), e);
+            failed += 1;
+            continue;
+        }
+        // A rename the journal does not know about could not be reverted
+        if let Err(e) = safety.record_rename(&issue.path, &target) {
+            error!("✗ Could not journal the rename of {}: {}", escaped_path(&issue.path), e);
+            if let Err(e) = fs::rename(&target, &issue.path) {
+                error!("✗ Failed to undo the rename of {}: {}", escaped_path(&issue.path), e);
+            }
+            failed += 1;
+        }
+    }
+    failed
+}
+
+/// Exit status of --check for the scanned files, reporting the number of
+/// issues that make it fail.
+fn check_status(files: &[FileReport]) -> i32 {
+    let issues = security::count_check_failures(files);
+    if issues == 0 {
+        return 0;
+    }
+    eprintln!("Security check failed: {} issue(s) found", issues);
+    EXIT_CHECK_FAILED
+}
+
+/// Converts stdin to stdout and returns the process exit status.
+fn run_filter(from: Option<&str>, target_encoding: &str, options: &ConversionOptions) -> i32 {
+    let mut input = Vec::new();
+    if let Err(e) = io::stdin().read_to_end(&mut input) {
+        eprintln!("Error reading stdin: {}", e);
+        return 1;
+    }
+    filter(&input, from, target_encoding, options, &mut io::stdout().lock())
+}
+
+/// Converts `input` into `output`, which receives nothing but the converted
+/// content. Errors go to stderr; the return value is the exit status.
+fn filter(
+    input: &[u8],
+    from: Option<&str>,
+    target_encoding: &str,
+    options: &ConversionOptions,
+    output: &mut impl Write,
+) -> i32 {
+    if input.is_empty() {
+        return 0;
+    }
+
+    let encoding = match from {
+        Some(name) => FileEncoding { encoding: name.to_string(), bom: None },
+        None => {
+            let detected = detect_encoding_from_bytes(input);
+            info!("Detected input encoding: {}", detected.encoding);
+            detected
+        }
+    };
+
+    match EncodingConverter::convert(input, &encoding, target_encoding, options) {
+        Ok(converted) => {
+            if let Err(e) = output.write_all(&converted).and_then(|_| output.flush()) {
+                eprintln!("Error writing stdout: {}", e);
+                return 1;
+            }
+            0
+        }
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            match e {
+                ConversionError::DecodeError(_) => EXIT_DECODE_FAILED,
+                ConversionError::EncodingError(_) => EXIT_ENCODE_FAILED,
+                ConversionError::RepairRejected(_) => EXIT_REPAIR_REJECTED,
+                _ => 1,
+            }
+        }
     }
-    Ok(())
 }
 
 fn main() -> Result<(), Box<dyn Error>> {
     let args = Args::parse();
-    let path = Path::new(&args.path);
-    
-    if !path.exists() {
-        eprintln!("Error: Path '{}' does not exist", path.display());
-        std::process::exit(1);
-    }
 
     let format = OutputFormat::from_str(&args.format).ok_or_else(|| {
-        format!("Invalid output format: '{}'. Valid formats are: text, json, csv", args.format)
+        format!("Invalid output format: '{}'. Valid formats are: text, json, ndjson, csv, sarif, junit, html", args.format)
     })?;
 
     let line_ending = LineEnding::from_str(&args.line_ending).ok_or_else(|| {
         format!("Invalid line ending: '{}'. Valid options are: unix, windows, keep", args.line_ending)
     })?;
 
-    let filter = FileFilter::new(args.extensions);
-    let mut stats = Statistics::new();
+    let normalize = args.normalize.as_deref()
+        .map(|form| NormalizationForm::from_str(form).ok_or_else(|| {
+            format!("Invalid normalization form: '{}'. Valid forms are: nfc, nfd, nfkc, nfkd", form)
+        }))
+        .transpose()?;
 
-    match scan_directory(path, &mut stats, &filter) {
-        Ok(_) => {
-            if matches!(format, OutputFormat::Text) {
-                println!("\nDirectory scan completed successfully.");
-            }
-            stats.display_summary(&format)?;
+    let mojibake_confidence = selection::parse_confidence(&args.mojibake_confidence).ok_or_else(|| {
+        format!("Invalid confidence: '{}'. Use a fraction from 0 to 1 or a percentage", args.mojibake_confidence)
+    })?;
 
-            // Handle conversion if requested
-            if let Some(target_encoding) = args.convert_to {
-                let output_dir = args.output_dir.map(PathBuf::from)
-                    .unwrap_or_else(|| {
-                        let mut dir = path.to_path_buf();
-                        dir.push("converted");
-                        dir
-                    });
+    let options = ConversionOptions {
+        line_ending,
+        repair_mixed: args.repair_mixed,
+        repair_mojibake: args.repair_mojibake.unwrap_or(0),
+        mojibake_confidence,
+        rewrite_declarations: !args.keep_declarations,
+        properties: args.properties,
+        normalize,
+        strip_embedded_boms: args.strip_embedded_boms,
+        decompress: !args.no_decompress,
+    };
 
-                // Initialize safety features with input path as base directory
-                let safety = ConversionSafety::new(path, &output_dir, args.create_backup)?;
+    let read_only = ReadOnlyPolicy::from_str(&args.read_only).ok_or_else(|| {
+        format!("Invalid read-only policy: '{}'. Valid policies are: skip, report, lift", args.read_only)
+    })?;
 
-                println!("\nConverting files to {} with {} line endings...", 
-                    target_encoding,
-                    match line_ending {
-                        LineEnding::Unix => "Unix",
-                        LineEnding::Windows => "Windows",
-                        LineEnding::Keep => "original",
-                    }
-                );
+    let preserve = PreserveOptions {
+        mode: !args.no_preserve_mode,
+        times: !args.no_preserve_times,
+        ownership: !args.no_preserve_ownership,
+        xattrs: !args.no_preserve_xattrs,
+    };
 
-                if args.skip_verification {
-                    println!("Warning: Verification is disabled. Conversion errors may not be detected.");
-                }
+    if args.report_file.is_some() && !matches!(format, OutputFormat::Junit | OutputFormat::Html) {
+        return Err("--report-file is only supported with the junit and html formats".into());
+    }
 
-                if args.create_backup {
-                    println!("Backups will be created before conversion.");
-                }
+    let from = args.from.as_deref().map(config::source_encoding_name).transpose()?;
+
+    if let (true, Some(target_encoding)) = (args.filter, &args.convert_to) {
+        safety::init_stderr_logging()?;
+        std::process::exit(run_filter(from.as_deref(), target_encoding, &options));
+    }
+
+    let paths: Vec<PathBuf> = args.path.iter().map(PathBuf::from).collect();
+    for path in &paths {
+        if !path.exists() {
+            eprintln!("Error: Path '{}' does not exist", path.display());
+            std::process::exit(1);
+        }
+    }
+    let listed = match args.files_from.as_deref() {
+        Some("-") => {
+            let mut content = Vec::new();
+            io::stdin().read_to_end(&mut content)?;
+            scanner::parse_path_list(&content)
+        }
+        Some(file) => scanner::parse_path_list(&fs::read(file)?),
+        None => Vec::new(),
+    };
+    // Listed paths are taken relative to the current directory
+    let mut roots: Vec<PathBuf> = paths.iter().map(|path| scanner::root_dir(path)).collect();
+    if args.files_from.is_some() {
+        roots.push(PathBuf::new());
+    }
+
+    let config = match &args.config {
+        Some(file) => Config::load(Path::new(file))?,
+        None => Config::default(),
+    };
+    let overrides = SourceOverrides::new(&roots, from, &config)?;
+
+    let size = |arg: &Option<String>| arg.as_deref()
+        .map(|s| filter::parse_size(s).ok_or_else(|| {
+            format!("Invalid size: '{}'. Use a byte count with an optional k, M or G suffix", s)
+        }))
+        .transpose();
+    let time = |arg: &Option<String>| arg.as_deref()
+        .map(|s| filter::parse_time(s).ok_or_else(|| {
+            format!("Invalid date: '{}'. Use YYYY-MM-DD or an RFC 3339 timestamp", s)
+        }))
+        .transpose();
+    let predicates = [
+        args.max_depth.map(Predicate::MaxDepth),
+        args.skip_hidden.then_some(Predicate::SkipHidden),
+        size(&args.min_size)?.map(Predicate::MinSize),
+        size(&args.max_size)?.map(Predicate::MaxSize),
+        time(&args.modified_since)?.map(Predicate::ModifiedSince),
+        time(&args.modified_before)?.map(Predicate::ModifiedBefore),
+    ];
+    let filter = predicates.into_iter()
+        .flatten()
+        .fold(FileFilter::new(args.extensions), FileFilter::with);
+    let bom = args.select_bom.as_deref()
+        .map(|s| match s.to_lowercase().as_str() {
+            "yes" => Ok(true),
+            "no" => Ok(false),
+            _ => Err(format!("Invalid BOM selector: '{}'. Valid options are: yes, no", s)),
+        })
+        .transpose()?;
+    let line_endings = args.select_line_endings.as_deref()
+        .map(|list| selection::parse_line_endings(list).ok_or_else(|| {
+            format!("Invalid line endings: '{}'. Valid styles are: lf, crlf, cr, mixed, none", list.join(","))
+        }))
+        .transpose()?;
+    let confidence = |arg: &Option<String>| arg.as_deref()
+        .map(|s| selection::parse_confidence(s).ok_or_else(|| {
+            format!("Invalid confidence: '{}'. Use a fraction from 0 to 1 or a percentage", s)
+        }))
+        .transpose();
+    let selectors = [
+        args.select_encoding.clone().map(Selector::Encoding),
+        bom.map(Selector::Bom),
+        line_endings.map(Selector::LineEndings),
+        confidence(&args.min_confidence)?.map(Selector::MinConfidence),
+        confidence(&args.max_confidence)?.map(Selector::MaxConfidence),
+    ];
+    let selection = selectors.into_iter()
+        .flatten()
+        .fold(Selection::default(), Selection::with);
+
+    let expected_encodings = args.expect_encoding.iter()
+        .flatten()
+        .map(|name| declared_encoding_name(name).ok_or_else(|| format!("Invalid expected encoding: '{}'", name)))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let mut stats = Statistics::with_selection(selection);
+    if matches!(format, OutputFormat::Ndjson) {
+        // Reports are only needed afterwards to convert or check the files
+        stats.set_streaming(args.convert_to.is_some() || args.check);
+    }
+    let analysis_options = AnalysisOptions {
+        trust_declarations: args.trust_declarations,
+        // SARIF consumers expect invisible characters to be reported
+        security_scan: args.security || args.check || matches!(format, OutputFormat::Sarif),
+        nfc_filenames: args.filename_nfc,
+        scan_archives: args.scan_archives || args.convert_archives,
+        decompress: !args.no_decompress,
+        overrides: &overrides,
+        expected_encodings: &expected_encodings,
+        target_encoding: args.convert_to.as_deref(),
+    };
+
+    let scan_options = ScanOptions {
+        follow_symlinks: !args.no_follow,
+    };
+    let mut scanner = Scanner::new(&mut stats, &filter, &analysis_options, scan_options);
+    // A root that cannot be scanned is reported without holding up the others
+    let mut failed_roots = 0;
+    for path in &paths {
+        if let Err(e) = scanner.scan(path) {
+            eprintln!("Error scanning {}: {}", path.display(), e);
+            failed_roots += 1;
+        }
+    }
+    for path in &listed {
+        scanner.scan_listed(path);
+    }
+    // Whoever read the streamed records is gone, so nobody sees the rest
+    if stats.output_closed() {
+        return Ok(());
+    }
+
+    if matches!(format, OutputFormat::Text) && failed_roots == 0 {
+        println!("\nDirectory scan completed successfully.");
+    }
+    // JUnit and HTML reports include the conversion results, so they
+    // wait until the files are converted
+    let report_after_conversion = matches!(format, OutputFormat::Junit | OutputFormat::Html);
+    if !report_after_conversion {
+        stats.display_summary(&format)?;
+    }
+    // A dry run writes nothing, not even the log file
+    let writes_files = !args.dry_run && (args.convert_to.is_some() || args.rename_filenames);
+    // An HTML report of a run that writes files goes next to its log. A
+    // report written to stdout has to be the only thing there, so the
+    // progress messages and the log go to stderr instead
+    let html_to_output_dir = matches!(format, OutputFormat::Html) && writes_files && args.report_file.is_none();
+    let report_on_stdout = match format {
+        OutputFormat::Text => false,
+        OutputFormat::Junit | OutputFormat::Html => args.report_file.is_none() && !html_to_output_dir,
+        OutputFormat::Json | OutputFormat::Ndjson | OutputFormat::Csv | OutputFormat::Sarif => true,
+    };
+    let mut progress: Box<dyn Write> = if report_on_stdout { Box::new(io::stderr()) } else { Box::new(io::stdout()) };
+
+    let output_dir = args.output_dir.map(PathBuf::from)
+        .unwrap_or_else(|| default_output_dir(&roots));
+
+    if let (true, Some(target_encoding)) = (args.dry_run, &args.convert_to) {
+        let plans: Vec<_> = stats.get_files().iter()
+            .filter(|f| f.archive.is_none())
+            .map(|f| plan::plan_conversion(f, target_encoding, &options, &output_dir, args.diff))
+            .collect();
+        output::write_plan(&plans, &format)?;
+        if args.rename_filenames {
+            writeln!(progress, "\nDry run: {} file name(s) would be renamed", stats.get_filename_issues().len())?;
+        }
+    }
+
+    // Initialize safety features with the input roots as base directories
+    let safety = if writes_files {
+        Some(ConversionSafety::new(roots.clone(), &output_dir, args.create_backup, preserve, report_on_stdout)?)
+    } else {
+        None
+    };
+
+    // Handle conversion if requested
+    let mut conversions = Vec::new();
+    if let (Some(target_encoding), Some(safety)) = (&args.convert_to, &safety) {
+        writeln!(progress, "\nConverting files to {} with {} line endings...", 
+            target_encoding,
+            match line_ending {
+                LineEnding::Unix => "Unix",
+                LineEnding::Windows => "Windows",
+                LineEnding::Keep => "original",
+            }
+        )?;
+
+        if args.skip_verification {
+            writeln!(progress, "Warning: Verification is disabled. Conversion errors may not be detected.")?;
+        }
+
+        if args.create_backup {
+            writeln!(progress, "Backups will be created before conversion.")?;
+        }
 
-                convert_files(
-                    &stats.get_files(),
-                    &target_encoding,
-                    line_ending,
-                    &output_dir,
-                    &safety,
-                    args.skip_verification
+        conversions = convert_files(
+            stats.get_files(),
+            target_encoding,
+            &options,
+            &output_dir,
+            safety,
+            &BatchOptions {
+                skip_verification: args.skip_verification,
+                read_only,
+                preserve_hardlinks: args.preserve_hardlinks,
+            }
+        )?;
+        if args.convert_archives {
+            conversions.extend(convert_archives(stats.get_files(), target_encoding, &options, &output_dir, safety));
+        }
+        let count = |status| conversions.iter().filter(|r| r.status == status).count();
+        writeln!(progress, "\nConverted: {}, already compliant: {}, skipped: {}, failed: {}",
+            count(ConversionStatus::Converted),
+            count(ConversionStatus::AlreadyCompliant),
+            count(ConversionStatus::Skipped),
+            count(ConversionStatus::Failed)
+        )?;
+        for result in &conversions {
+            if let Some(error) = &result.error {
+                writeln!(progress, "  {} {} ({}): {}",
+                    if result.status == ConversionStatus::Skipped { "-" } else { "✗" },
+                    escaped_path(&result.path),
+                    error.kind.as_str(),
+                    error.message
                 )?;
+            }
+        }
+
+
+        writeln!(progress, "\nConversion completed.")?;
+        writeln!(progress, "Output directory: {}", output_dir.display())?;
+    }
 
-                println!("\nConversion completed.");
-                println!("Output directory: {}", output_dir.display());
+    if let (true, Some(safety)) = (args.rename_filenames, &safety) {
+        writeln!(progress, "\nRenaming {} file names...", stats.get_filename_issues().len())?;
+        let failed = rename_filenames(stats.get_filename_issues(), safety);
+        if failed > 0 {
+            writeln!(progress, "Failed to rename {} file name(s), see the log for details", failed)?;
+        }
+        if let Some(journal) = safety.get_rename_journal() {
+            writeln!(progress, "Rename journal: {}", journal.display())?;
+        }
+    }
+
+    if let Some(safety) = &safety {
+        if let Some(backup_dir) = safety.get_backup_dir() {
+            writeln!(progress, "Backup directory: {}", backup_dir.display())?;
+        }
+        writeln!(progress, "Log file: {}", safety.get_log_file().display())?;
+    }
+
+    if report_after_conversion {
+        let report = stats.generate_report();
+        let rendered = if let OutputFormat::Html = format {
+            let mut links = Vec::new();
+            if let Some(safety) = &safety {
+                links.push(("Conversion log", safety.get_log_file().to_path_buf()));
                 if let Some(backup_dir) = safety.get_backup_dir() {
-                    println!("Backup directory: {}", backup_dir.display());
+                    links.push(("Backups", backup_dir.to_path_buf()));
+                }
+                if let Some(journal) = safety.get_rename_journal() {
+                    links.push(("Rename journal", journal.to_path_buf()));
                 }
-                println!("Log file: {}", safety.get_log_file().display());
             }
+            let log = safety.as_ref()
+                .and_then(|safety| fs::read_to_string(safety.get_log_file()).ok())
+                .unwrap_or_default();
+            html::render_html(&report, &conversions, &links, &log)?
+        } else {
+            junit::render_junit(&report, &conversions)?
+        };
+        let report_file = args.report_file.as_ref().map(PathBuf::from).or_else(|| {
+            safety.as_ref()
+                .filter(|_| html_to_output_dir)
+                .map(|safety| safety.get_report_file().to_path_buf())
+        });
+        match report_file {
+            Some(file) => {
+                fs::write(&file, rendered)?;
+                println!("Report written to {}", file.display());
+            }
+            None => println!("{}", rendered),
+        }
+    }
 
-            Ok(())
+    if args.check {
+        let status = check_status(stats.get_files());
+        if status != 0 {
+            std::process::exit(status);
         }
-        Err(e) => {
-            eprintln!("Error scanning directory: {}", e);
-            std::process::exit(1);
+    }
+
+    if failed_roots > 0 {
+        eprintln!("{} of {} path(s) could not be scanned", failed_roots, paths.len());
+        std::process::exit(1);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{analyze, conversion_options, TempDir};
+
+    #[test]
+    fn default_output_dir_is_below_the_common_parent_of_all_roots() {
+        assert_eq!(default_output_dir(&[PathBuf::from("src")]), PathBuf::from("src/converted"));
+        assert_eq!(
+            default_output_dir(&[PathBuf::from("/data/a/x"), PathBuf::from("/data/b"), PathBuf::from("/data/a")]),
+            PathBuf::from("/data/converted"),
+        );
+        let current = std::env::current_dir().unwrap();
+        assert_eq!(
+            default_output_dir(&[current.join("docs"), PathBuf::new()]),
+            current.join("converted"),
+        );
+    }
+
+    fn convert(dir: &TempDir, files: &[FileReport], output_dir: &Path) -> Vec<(ConversionStatus, Vec<u8>)> {
+        let batch = BatchOptions { skip_verification: false, read_only: ReadOnlyPolicy::Report, preserve_hardlinks: false };
+        convert_with(dir, files, output_dir, &batch)
+    }
+
+    fn convert_with(dir: &TempDir, files: &[FileReport], output_dir: &Path, batch: &BatchOptions) -> Vec<(ConversionStatus, Vec<u8>)> {
+        let preserve = PreserveOptions { mode: true, times: true, ownership: false, xattrs: false };
+        let safety = ConversionSafety::without_logging(vec![dir.path().join("in")], &dir.path().join("logs"), false, preserve).unwrap();
+        convert_files(files, "UTF-8", &conversion_options(), output_dir, &safety, batch).unwrap()
+            .into_iter()
+            .map(|result| (result.status, fs::read(output_dir.join(result.path.file_name().unwrap())).unwrap()))
+            .collect()
+    }
+
+    #[test]
+    fn converts_into_a_separate_output_dir_and_copies_compliant_files() {
+        let dir = TempDir::new();
+        let files = [
+            analyze(&dir.write("in/latin.txt", b"caf\xe9\n")),
+            analyze(&dir.write("in/ascii.txt", "plain\n")),
+        ];
+        let out = dir.path().join("out");
+        assert_eq!(convert(&dir, &files, &out), [
+            (ConversionStatus::Converted, "caf\u{e9}\n".as_bytes().to_vec()),
+            (ConversionStatus::AlreadyCompliant, b"plain\n".to_vec()),
+        ]);
+        // The sources are left as they were
+        assert_eq!(fs::read(dir.path().join("in/latin.txt")).unwrap(), b"caf\xe9\n");
+    }
+
+    #[test]
+    fn leaves_compliant_files_alone_in_place() {
+        let dir = TempDir::new();
+        let path = dir.write("in/ascii.txt", "plain\n");
+        let modified = fs::metadata(&path).unwrap().modified().unwrap();
+        let files = [analyze(&path)];
+        assert_eq!(convert(&dir, &files, &dir.path().join("in")), [
+            (ConversionStatus::AlreadyCompliant, b"plain\n".to_vec()),
+        ]);
+        assert_eq!(fs::metadata(&path).unwrap().modified().unwrap(), modified);
+    }
+
+    #[test]
+    fn applies_the_read_only_policy_to_existing_outputs() {
+        let expected = [
+            (ReadOnlyPolicy::Skip, ConversionStatus::Skipped, b"old\n".to_vec()),
+            (ReadOnlyPolicy::Report, ConversionStatus::Failed, b"old\n".to_vec()),
+            (ReadOnlyPolicy::Lift, ConversionStatus::Converted, "caf\u{e9}\n".as_bytes().to_vec()),
+        ];
+        for (read_only, status, content) in expected {
+            let dir = TempDir::new();
+            let files = [analyze(&dir.write("in/latin.txt", b"caf\xe9\n"))];
+            let output = dir.write("out/latin.txt", "old\n");
+            let mut permissions = fs::metadata(&output).unwrap().permissions();
+            permissions.set_readonly(true);
+            fs::set_permissions(&output, permissions).unwrap();
+
+            let batch = BatchOptions { skip_verification: false, read_only, preserve_hardlinks: false };
+            assert_eq!(convert_with(&dir, &files, &dir.path().join("out"), &batch), [(status, content)], "{:?}", read_only);
+            // Lifting is only for the conversion
+            assert!(access::is_read_only(&output), "{:?}", read_only);
         }
     }
+
+    #[test]
+    fn gives_hard_links_one_converted_output() {
+        for preserve_hardlinks in [true, false] {
+            let dir = TempDir::new();
+            let first = dir.write("in/a.txt", b"caf\xe9\n");
+            fs::hard_link(&first, dir.path().join("in/b.txt")).unwrap();
+            let files = [analyze(&first), analyze(&dir.path().join("in/b.txt"))];
+            let out = dir.path().join("out");
+
+            let batch = BatchOptions { skip_verification: false, read_only: ReadOnlyPolicy::Report, preserve_hardlinks };
+            let converted = (ConversionStatus::Converted, "caf\u{e9}\n".as_bytes().to_vec());
+            assert_eq!(convert_with(&dir, &files, &out, &batch), [converted.clone(), converted]);
+            let linked = file_id(&out.join("a.txt")) == file_id(&out.join("b.txt"));
+            assert_eq!(linked, preserve_hardlinks);
+        }
+    }
+
+    /// Runs the filter and returns its exit status and output.
+    fn run(input: &[u8], from: Option<&str>, to: &str, options: &ConversionOptions) -> (i32, Vec<u8>) {
+        let mut output = Vec::new();
+        let status = filter(input, from, to, options, &mut output);
+        (status, output)
+    }
+
+    #[test]
+    fn filter_writes_only_the_converted_content() {
+        assert_eq!(run(b"caf\xe9\n", None, "UTF-8", &conversion_options()), (0, "caf\u{e9}\n".as_bytes().to_vec()));
+        assert_eq!(run(b"", None, "UTF-8", &conversion_options()), (0, Vec::new()));
+    }
+
+    #[test]
+    fn filter_exit_statuses() {
+        // Not valid UTF-8
+        assert_eq!(run(b"caf\xe9\n", Some("UTF-8"), "UTF-16LE", &conversion_options()), (EXIT_DECODE_FAILED, Vec::new()));
+        // No room for Chinese in Latin-1
+        assert_eq!(run("\u{4e2d}\u{6587}\n".as_bytes(), None, "ISO-8859-1", &conversion_options()), (EXIT_ENCODE_FAILED, Vec::new()));
+        // Undoing the double encoding of \u{80} would produce a C1 control
+        let repair = ConversionOptions { repair_mojibake: 3, ..conversion_options() };
+        let input = "Gr\u{c3}\u{b6}\u{c3}\u{178}e und W\u{c3}\u{a4}rme, \u{c2}\u{20ac} sch\u{c3}\u{b6}n\n";
+        assert_eq!(run(input.as_bytes(), None, "UTF-8", &repair), (EXIT_REPAIR_REJECTED, Vec::new()));
+    }
+
+    #[test]
+    fn check_fails_with_its_own_exit_status() {
+        let overrides = SourceOverrides::new(&[], None, &Config::default()).unwrap();
+        let options = AnalysisOptions {
+            trust_declarations: false,
+            security_scan: true,
+            nfc_filenames: false,
+            scan_archives: false,
+            decompress: false,
+            overrides: &overrides,
+            expected_encodings: &[],
+            target_encoding: None,
+        };
+        let report = |content: &str| analysis::analyze_bytes(PathBuf::from("a.rs"), "a.rs".to_string(), content.as_bytes(), &options);
+        assert_eq!(check_status(&[report("let ok = 1;\n")]), 0);
+        assert_eq!(check_status(&[report("let ok = 1;\n"), report("// \u{202E}\n")]), EXIT_CHECK_FAILED);
+    }
 }
@@ -0,0 +1,116 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+use filetime::FileTime;
+
+/// Which attributes of the source file are carried over to converted
+/// files and backups.
+#[derive(Debug, Clone, Copy)]
+pub struct PreserveOptions {
+    pub mode: bool,
+    pub times: bool,
+    pub ownership: bool,
+    pub xattrs: bool,
+}
+
+/// Copies the selected metadata from `source` to `target`. A read-only
+/// mode is copied like any other; overwriting such a target later is up
+/// to the --read-only policy. Ownership and extended attributes are
+/// skipped where the process or file system does not allow them. Timestamps go last because the other steps touch ctime
+/// and, on some systems, mtime.
+pub fn copy_metadata(source: &Path, target: &Path, options: &PreserveOptions) -> io::Result<()> {
+    let metadata = fs::metadata(source)?;
+    if options.ownership {
+        copy_ownership(&metadata, target)?;
+    }
+    if options.xattrs {
+        copy_xattrs(source, target)?;
+    }
+    // After xattrs, since a read-only mode would block setting them
+    if options.mode {
+        fs::set_permissions(target, metadata.permissions())?;
+    }
+    if options.times {
+        filetime::set_file_times(
+            target,
+            FileTime::from_last_access_time(&metadata),
+            FileTime::from_last_modification_time(&metadata),
+        )?;
+    }
+    Ok(())
+}
+
+#[cfg(unix)]
+fn copy_ownership(metadata: &fs::Metadata, target: &Path) -> io::Result<()> {
+    use std::os::unix::fs::MetadataExt;
+    // Only root may give files away; keep the current owner otherwise
+    match std::os::unix::fs::chown(target, Some(metadata.uid()), Some(metadata.gid())) {
+        Err(e) if e.kind() == io::ErrorKind::PermissionDenied => Ok(()),
+        result => result,
+    }
+}
+
+#[cfg(not(unix))]
+fn copy_ownership(_metadata: &fs::Metadata, _target: &Path) -> io::Result<()> {
+    Ok(())
+}
+
+#[cfg(unix)]
+fn copy_xattrs(source: &Path, target: &Path) -> io::Result<()> {
+    let names = match xattr::list(source) {
+        Ok(names) => names,
+        Err(e) if e.kind() == io::ErrorKind::Unsupported => return Ok(()),
+        Err(e) => return Err(e),
+    };
+    for name in names {
+        if let Some(value) = xattr::get(source, &name)? {
+            match xattr::set(target, &name, &value) {
+                // Namespaces such as security.* need privileges
+                Err(e) if matches!(e.kind(), io::ErrorKind::PermissionDenied | io::ErrorKind::Unsupported) => {}
+                result => result?,
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn copy_xattrs(_source: &Path, _target: &Path) -> io::Result<()> {
+    Ok(())
+}
+
+/// Device and inode of the file behind a path, so hard links and symlinks
+/// to the same file can be recognised.
+#[cfg(unix)]
+pub fn file_id(path: &Path) -> Option<(u64, u64)> {
+    use std::os::unix::fs::MetadataExt;
+    let metadata = fs::metadata(path).ok()?;
+    Some((metadata.dev(), metadata.ino()))
+}
+
+#[cfg(not(unix))]
+pub fn file_id(_path: &Path) -> Option<(u64, u64)> {
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TempDir;
+
+    #[test]
+    fn copies_the_mode_only_when_asked() {
+        let dir = TempDir::new();
+        let source = dir.write("source.txt", "text");
+        let mut permissions = fs::metadata(&source).unwrap().permissions();
+        permissions.set_readonly(true);
+        fs::set_permissions(&source, permissions).unwrap();
+
+        for mode in [false, true] {
+            let target = dir.write(format!("target-{}.txt", mode), "text");
+            let options = PreserveOptions { mode, times: true, ownership: false, xattrs: false };
+            copy_metadata(&source, &target, &options).unwrap();
+            assert_eq!(fs::metadata(&target).unwrap().permissions().readonly(), mode);
+        }
+    }
+}
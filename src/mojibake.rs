@@ -0,0 +1,159 @@
+use encoding_rs::{UTF_8, WINDOWS_1252};
+
+/// Double-encoding patterns found in a piece of text.
+#[derive(Debug)]
+pub struct MojibakeReport {
+    pub layers: usize,
+    pub suspicious: usize,
+    pub confidence: f64,
+    /// Byte offset of the first suspicious sequence
+    pub first_offset: usize,
+}
+
+// Deeper nesting than this is practically never seen in the wild
+const MAX_DETECTED_LAYERS: usize = 4;
+
+pub fn detect_mojibake(text: &str) -> Option<MojibakeReport> {
+    let (first_offset, suspicious, clean) = scan_runs(text);
+    let first_offset = first_offset?;
+
+    let ratio = suspicious as f64 / (suspicious + clean) as f64;
+    // A single matching run can be a coincidence, many cannot
+    let confidence = ratio * (1.0 - 1.0 / (suspicious as f64 + 1.0));
+
+    let mut layers = 1;
+    let mut current = repair_layer(text);
+    while layers < MAX_DETECTED_LAYERS && scan_runs(&current).0.is_some() {
+        layers += 1;
+        current = repair_layer(&current);
+    }
+
+    Some(MojibakeReport {
+        layers,
+        suspicious,
+        confidence,
+        first_offset,
+    })
+}
+
+/// Reverses up to `max_layers` layers of UTF-8 read as Windows-1252.
+/// Returns the repaired text and the number of layers removed, or an error
+/// if the result does not look like plausible text. Text whose detection
+/// confidence is below `min_confidence` is returned unchanged, since a few
+/// coincidental matches in correct text must not be rewritten.
+pub fn repair_mojibake(text: &str, max_layers: usize, min_confidence: f64) -> Result<(String, usize), String> {
+    if detect_mojibake(text).is_none_or(|report| report.confidence < min_confidence) {
+        return Ok((text.to_string(), 0));
+    }
+    let mut current = text.to_string();
+    let mut layers = 0;
+    while layers < max_layers && scan_runs(&current).0.is_some() {
+        current = repair_layer(&current);
+        layers += 1;
+    }
+
+    if layers > 0 {
+        verify_plausible(text, &current)?;
+    }
+    Ok((current, layers))
+}
+
+/// A genuine repair never introduces replacement or C1 control characters
+/// that were not already present in the input.
+fn verify_plausible(original: &str, repaired: &str) -> Result<(), String> {
+    let implausible = |text: &str| text.chars()
+        .filter(|&c| c == '\u{FFFD}' || matches!(c, '\u{80}'..='\u{9F}'))
+        .count();
+    let before = implausible(original);
+    let after = implausible(repaired);
+    if after > before {
+        return Err(format!(
+            "repair introduced {} replacement or control character(s)",
+            after - before
+        ));
+    }
+    Ok(())
+}
+
+/// Returns the offset of the first suspicious run and the number of
+/// suspicious and clean runs of non-ASCII characters.
+fn scan_runs(text: &str) -> (Option<usize>, usize, usize) {
+    let mut first = None;
+    let mut suspicious = 0;
+    let mut clean = 0;
+    for (start, run) in non_ascii_runs(text) {
+        if unmangle(run).is_some() {
+            first.get_or_insert(start);
+            suspicious += 1;
+        } else {
+            clean += 1;
+        }
+    }
+    (first, suspicious, clean)
+}
+
+fn repair_layer(text: &str) -> String {
+    let mut result = String::with_capacity(text.len());
+    let mut last = 0;
+    for (start, run) in non_ascii_runs(text) {
+        if let Some(fixed) = unmangle(run) {
+            result.push_str(&text[last..start]);
+            result.push_str(&fixed);
+            last = start + run.len();
+        }
+    }
+    result.push_str(&text[last..]);
+    result
+}
+
+fn non_ascii_runs(text: &str) -> impl Iterator<Item = (usize, &str)> {
+    let mut rest = text.char_indices().peekable();
+    std::iter::from_fn(move || {
+        let (start, _) = rest.find(|(_, c)| !c.is_ascii())?;
+        let mut end = text.len();
+        while let Some(&(i, c)) = rest.peek() {
+            if c.is_ascii() {
+                end = i;
+                break;
+            }
+            rest.next();
+        }
+        Some((start, &text[start..end]))
+    })
+}
+
+/// Re-encodes a run as Windows-1252 and reads the bytes as UTF-8, which
+/// undoes exactly one layer of mis-decoding.
+fn unmangle(run: &str) -> Option<String> {
+    let (bytes, _, had_errors) = WINDOWS_1252.encode(run);
+    if had_errors {
+        return None;
+    }
+    let (decoded, had_errors) = UTF_8.decode_without_bom_handling(&bytes);
+    if had_errors || decoded == run {
+        return None;
+    }
+    Some(decoded.into_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn repairs_confident_double_encoding() {
+        let text = "GrÃ¶ÃŸe und WÃ¤rme, schÃ¶n";
+        let (repaired, layers) = repair_mojibake(text, 3, 0.6).unwrap();
+        assert_eq!(repaired, "Größe und Wärme, schön");
+        assert_eq!(layers, 1);
+    }
+
+    #[test]
+    fn leaves_text_below_threshold_alone() {
+        // One coincidental match among several correct non-ASCII words
+        let text = "café naïve résumé Ã© déjà";
+        let (repaired, layers) = repair_mojibake(text, 3, 0.6).unwrap();
+        assert_eq!(repaired, text);
+        assert_eq!(layers, 0);
+    }
+}
@@ -0,0 +1,91 @@
+use serde::Serialize;
+use unicode_normalization::{is_nfc, is_nfd, UnicodeNormalization};
+
+/// Normalization state of a file's text, reported only for text that
+/// contains characters with distinct composed and decomposed forms.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum NormalizationState {
+    #[serde(rename = "NFC")]
+    Nfc,
+    #[serde(rename = "NFD")]
+    Nfd,
+    #[serde(rename = "mixed")]
+    Mixed,
+}
+
+impl NormalizationState {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            NormalizationState::Nfc => "NFC",
+            NormalizationState::Nfd => "NFD",
+            NormalizationState::Mixed => "mixed",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum NormalizationForm {
+    Nfc,
+    Nfd,
+    Nfkc,
+    Nfkd,
+}
+
+impl NormalizationForm {
+    pub fn from_str(s: &str) -> Option<Self> {
+        match s.to_lowercase().as_str() {
+            "nfc" => Some(NormalizationForm::Nfc),
+            "nfd" => Some(NormalizationForm::Nfd),
+            "nfkc" => Some(NormalizationForm::Nfkc),
+            "nfkd" => Some(NormalizationForm::Nfkd),
+            _ => None,
+        }
+    }
+
+    pub fn apply(&self, text: &str) -> String {
+        match self {
+            NormalizationForm::Nfc => text.nfc().collect(),
+            NormalizationForm::Nfd => text.nfd().collect(),
+            NormalizationForm::Nfkc => text.nfkc().collect(),
+            NormalizationForm::Nfkd => text.nfkd().collect(),
+        }
+    }
+}
+
+pub fn detect_normalization(text: &str) -> Option<NormalizationState> {
+    if text.is_ascii() {
+        return None;
+    }
+    match (is_nfc(text), is_nfd(text)) {
+        // Nothing in the text composes or decomposes
+        (true, true) => None,
+        (true, false) => Some(NormalizationState::Nfc),
+        (false, true) => Some(NormalizationState::Nfd),
+        (false, false) => Some(NormalizationState::Mixed),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detects_the_normalization_state() {
+        assert_eq!(detect_normalization("plain ascii"), None);
+        assert_eq!(detect_normalization("日本語"), None);
+        assert_eq!(detect_normalization("caf\u{e9}"), Some(NormalizationState::Nfc));
+        assert_eq!(detect_normalization("cafe\u{301}"), Some(NormalizationState::Nfd));
+        assert_eq!(detect_normalization("caf\u{e9} cafe\u{301}"), Some(NormalizationState::Mixed));
+    }
+
+    #[test]
+    fn applies_each_form() {
+        let text = "cafe\u{301} \u{fb01}le \u{2460}";
+        let apply = |name| NormalizationForm::from_str(name).unwrap().apply(text);
+        assert_eq!(apply("NFC"), "caf\u{e9} \u{fb01}le \u{2460}");
+        assert_eq!(apply("nfd"), "cafe\u{301} \u{fb01}le \u{2460}");
+        assert_eq!(apply("nfkc"), "caf\u{e9} file 1");
+        assert_eq!(apply("nfkd"), "cafe\u{301} file 1");
+        assert!(NormalizationForm::from_str("nfx").is_none());
+    }
+}
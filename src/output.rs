@@ -1,20 +1,87 @@
-use std::path::PathBuf;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
 use serde::Serialize;
-use crate::detection::FileEncoding;
+use crate::access::{FileError, ScanError};
+use crate::archive::ArchiveEntry;
+use crate::compression::Codec;
+use crate::detection::{EncodingDeclaration, EncodingSegment, FileEncoding, LineEndingStyle};
+use crate::filenames::{escaped_path, serialize_optional_path, serialize_path, FilenameIssue};
+use crate::findings::Finding;
+use crate::normalization::NormalizationState;
+use crate::plan::ConversionPlan;
+use crate::html::render_html;
+use crate::junit::render_junit;
+use crate::sarif::write_sarif_output;
 
 #[derive(Serialize, Clone)]
 pub struct FileReport {
+    /// Scan root the file was found under; reported paths are relative to it
+    #[serde(skip_serializing_if = "Option::is_none", serialize_with = "serialize_optional_path")]
+    pub root: Option<PathBuf>,
+    #[serde(serialize_with = "serialize_path")]
     pub path: PathBuf,
     pub name: String,
     #[serde(flatten)]
     pub encoding: FileEncoding,
+    /// How sure the encoding detection is, from 0 to 1
+    pub confidence: f64,
+    /// Present only when the file mixes several encodings
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub segments: Option<Vec<EncodingSegment>>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub declarations: Vec<EncodingDeclaration>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub normalization: Option<NormalizationState>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub line_endings: Option<LineEndingStyle>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub findings: Vec<Finding>,
+    /// Codec the content was decompressed with before analysis
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub compression: Option<Codec>,
+    /// Set for files read from inside an archive; `path` then has the form
+    /// `archive.zip!/path/in/archive`
+    #[serde(skip)]
+    pub archive: Option<ArchiveEntry>,
+    /// Why the file could not be read
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<FileError>,
 }
 
 #[derive(Serialize)]
 pub struct ScanReport {
     pub total_files: usize,
+    /// Files scanned but left out by the selectors
+    #[serde(skip_serializing_if = "is_zero")]
+    pub deselected_files: usize,
     pub files: Vec<FileReport>,
     pub encoding_stats: Vec<EncodingStat>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub filename_issues: Vec<FilenameIssue>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub errors: Vec<ScanError>,
+}
+
+/// One line of `ndjson` output. Files, scan errors and file name issues are
+/// written as they are found, and a summary closes the stream.
+#[derive(Serialize)]
+#[serde(tag = "type", rename_all = "kebab-case")]
+pub enum StreamRecord<'a> {
+    File(&'a FileReport),
+    Error(&'a ScanError),
+    FilenameIssue(&'a FilenameIssue),
+    Plan(&'a ConversionPlan),
+    Summary(StreamSummary<'a>),
+}
+
+#[derive(Serialize)]
+pub struct StreamSummary<'a> {
+    pub total_files: usize,
+    #[serde(skip_serializing_if = "is_zero")]
+    pub deselected_files: usize,
+    pub encoding_stats: &'a [EncodingStat],
+    pub errors: usize,
+    pub filename_issues: usize,
 }
 
 #[derive(Serialize, Clone)]
@@ -32,6 +99,13 @@ struct FileRecordCsv {
     name: String,
     encoding: String,
     bom: String,
+    segments: String,
+    normalization: String,
+    line_endings: String,
+    compression: String,
+    confidence: f64,
+    findings: String,
+    error: String,
 }
 
 #[derive(Serialize)]
@@ -42,10 +116,18 @@ struct StatRecordCsv {
     percentage: f64,
 }
 
+fn is_zero(n: &usize) -> bool {
+    *n == 0
+}
+
 pub enum OutputFormat {
     Text,
     Json,
+    Ndjson,
     Csv,
+    Sarif,
+    Junit,
+    Html,
 }
 
 impl OutputFormat {
@@ -53,7 +135,11 @@ impl OutputFormat {
         match s.to_lowercase().as_str() {
             "text" => Some(OutputFormat::Text),
             "json" => Some(OutputFormat::Json),
+            "ndjson" => Some(OutputFormat::Ndjson),
             "csv" => Some(OutputFormat::Csv),
+            "sarif" => Some(OutputFormat::Sarif),
+            "junit" => Some(OutputFormat::Junit),
+            "html" => Some(OutputFormat::Html),
             _ => None,
         }
     }
@@ -63,23 +149,76 @@ pub fn write_output(report: &ScanReport, format: &OutputFormat) -> Result<(), Bo
     match format {
         OutputFormat::Text => write_text_output(report),
         OutputFormat::Json => write_json_output(report),
+        // Everything but the summary was streamed during the scan
+        OutputFormat::Ndjson => write_record(&mut io::stdout().lock(), &summary_record(report)).map_err(Into::into),
         OutputFormat::Csv => write_csv_output(report),
+        OutputFormat::Sarif => write_sarif_output(report),
+        OutputFormat::Junit => {
+            println!("{}", render_junit(report, &[])?);
+            Ok(())
+        }
+        OutputFormat::Html => {
+            println!("{}", render_html(report, &[], &[], "")?);
+            Ok(())
+        }
     }
 }
 
 fn write_text_output(report: &ScanReport) -> Result<(), Box<dyn std::error::Error>> {
     println!("Files:");
+    let mut root = None;
     for file in &report.files {
+        if file.root.is_some() && file.root != root {
+            root = file.root.clone();
+            println!("📁 {}", escaped_path(root.as_deref().unwrap_or(Path::new(""))));
+        }
         let bom_info = file.encoding.bom.map_or("No BOM".to_string(), |b| format!("BOM: {}", b));
-        println!("📄 {} [{}, {}]", 
-            file.path.display(), 
+        let normalization = file.normalization
+            .map_or(String::new(), |n| format!(", {}", n.as_str()));
+        let compression = file.compression
+            .map_or(String::new(), |c| format!(", {}", c.as_str()));
+        let line_endings = file.line_endings
+            .map_or(String::new(), |l| format!(", {}", l.as_str()));
+        // Only guesses are worth flagging
+        let confidence = if file.confidence > 0.0 && file.confidence < 1.0 {
+            format!(", {:.0}% confidence", file.confidence * 100.0)
+        } else {
+            String::new()
+        };
+        println!("📄 {} [{}, {}{}{}{}{}]", 
+            escaped_path(&file.path), 
             file.encoding.encoding, 
-            bom_info
+            bom_info,
+            normalization,
+            line_endings,
+            compression,
+            confidence
         );
+        if let Some(segments) = &file.segments {
+            let ranges: Vec<String> = segments.iter()
+                .map(|s| format!("lines {}-{} {}", s.start_line, s.end_line, s.encoding))
+                .collect();
+            println!("   ↳ mixed encodings: {}", ranges.join(", "));
+        }
+        if let Some(error) = &file.error {
+            println!("   ✗ {}: {}", error.kind.as_str(), error.message);
+        }
+        for finding in &file.findings {
+            let location = match (finding.line, finding.column) {
+                (Some(line), Some(column)) => format!(" at {}:{}", line, column),
+                _ => String::new(),
+            };
+            let confidence = finding.confidence
+                .map_or(String::new(), |c| format!(", confidence {:.0}%", c * 100.0));
+            println!("   ⚠ {}{}: {}{}", finding.kind, location, finding.message, confidence);
+        }
     }
 
     println!("\n=== Encoding Statistics ===");
     println!("Total files scanned: {}", report.total_files);
+    if report.deselected_files > 0 {
+        println!("Files not matching the selectors: {}", report.deselected_files);
+    }
     println!("\nEncoding Distribution:");
     
     for stat in &report.encoding_stats {
@@ -91,6 +230,25 @@ fn write_text_output(report: &ScanReport) -> Result<(), Box<dyn std::error::Erro
             stat.percentage
         );
     }
+
+    if !report.errors.is_empty() {
+        println!("\n=== Scan Errors ===");
+        for error in &report.errors {
+            println!("✗ {} ({}): {}", escaped_path(&error.path), error.error.kind.as_str(), error.error.message);
+        }
+    }
+
+    if !report.filename_issues.is_empty() {
+        println!("\n=== Problematic File Names ===");
+        for issue in &report.filename_issues {
+            println!("- {} ({}, likely {}) → {}",
+                escaped_path(&issue.path),
+                issue.reason,
+                issue.likely_encoding,
+                issue.suggested_name
+            );
+        }
+    }
     Ok(())
 }
 
@@ -100,20 +258,52 @@ fn write_json_output(report: &ScanReport) -> Result<(), Box<dyn std::error::Erro
     Ok(())
 }
 
+/// The record that closes an `ndjson` stream.
+pub fn summary_record(report: &ScanReport) -> StreamRecord<'_> {
+    StreamRecord::Summary(StreamSummary {
+        total_files: report.total_files,
+        deselected_files: report.deselected_files,
+        encoding_stats: &report.encoding_stats,
+        errors: report.errors.len(),
+        filename_issues: report.filename_issues.len(),
+    })
+}
+
+/// Writes a single `ndjson` record on its own line. Fails instead of
+/// panicking when stdout is closed, e.g. by `| head`.
+pub fn write_record(out: &mut impl Write, record: &StreamRecord) -> io::Result<()> {
+    writeln!(out, "{}", serde_json::to_string(record)?)
+}
+
 fn write_csv_output(report: &ScanReport) -> Result<(), Box<dyn std::error::Error>> {
     println!("File Analysis:");
     let mut writer = csv::Writer::from_writer(std::io::stdout());
     
     // Write header manually for clarity
-    writer.write_record(&["Path", "Name", "Encoding", "BOM"])?;
+    writer.write_record(["Path", "Name", "Encoding", "BOM", "Segments", "Normalization", "Line Endings", "Compression", "Confidence", "Findings", "Error"])?;
     
     // Write files data
     for file in &report.files {
         let record = FileRecordCsv {
-            path: file.path.to_string_lossy().to_string(),
+            path: escaped_path(&file.path),
             name: file.name.clone(),
             encoding: file.encoding.encoding.clone(),
             bom: file.encoding.bom.map_or("No BOM".to_string(), |b| b.to_string()),
+            segments: file.segments.as_ref().map_or(String::new(), |segments| {
+                segments.iter()
+                    .map(|s| format!("{}-{}:{}", s.start_line, s.end_line, s.encoding))
+                    .collect::<Vec<_>>()
+                    .join(";")
+            }),
+            normalization: file.normalization.map_or(String::new(), |n| n.as_str().to_string()),
+            line_endings: file.line_endings.map_or(String::new(), |l| l.as_str().to_string()),
+            compression: file.compression.map_or(String::new(), |c| c.as_str().to_string()),
+            confidence: file.confidence,
+            findings: file.findings.iter()
+                .map(|f| f.kind)
+                .collect::<Vec<_>>()
+                .join(";"),
+            error: file.error.as_ref().map_or(String::new(), |e| e.kind.as_str().to_string()),
         };
         writer.serialize(record)?;
     }
@@ -124,7 +314,7 @@ fn write_csv_output(report: &ScanReport) -> Result<(), Box<dyn std::error::Error
     let mut stats_writer = csv::Writer::from_writer(std::io::stdout());
     
     // Write header for stats
-    stats_writer.write_record(&["Encoding", "BOM", "Count", "Percentage"])?;
+    stats_writer.write_record(["Encoding", "BOM", "Count", "Percentage"])?;
     
     for stat in &report.encoding_stats {
         let record = StatRecordCsv {
@@ -136,5 +326,158 @@ fn write_csv_output(report: &ScanReport) -> Result<(), Box<dyn std::error::Error
         stats_writer.serialize(record)?;
     }
     stats_writer.flush()?;
+
+    if !report.errors.is_empty() {
+        println!("\nScan Errors:");
+        let mut errors_writer = csv::Writer::from_writer(std::io::stdout());
+        errors_writer.write_record(["Path", "Error", "Message"])?;
+        for error in &report.errors {
+            errors_writer.write_record([
+                escaped_path(&error.path).as_str(),
+                error.error.kind.as_str(),
+                &error.error.message,
+            ])?;
+        }
+        errors_writer.flush()?;
+    }
+
+    if !report.filename_issues.is_empty() {
+        println!("\nProblematic File Names:");
+        let mut names_writer = csv::Writer::from_writer(std::io::stdout());
+        names_writer.write_record(["Path", "Reason", "Likely Encoding", "Suggested Name"])?;
+        for issue in &report.filename_issues {
+            names_writer.write_record([
+                escaped_path(&issue.path).as_str(),
+                issue.reason,
+                &issue.likely_encoding,
+                &issue.suggested_name,
+            ])?;
+        }
+        names_writer.flush()?;
+    }
+    Ok(())
+}
+
+pub fn write_plan(plans: &[ConversionPlan], format: &OutputFormat) -> Result<(), Box<dyn std::error::Error>> {
+    match format {
+        // The report formats have no notion of planned changes. JUnit and
+        // HTML reports may follow on stdout, so their plan goes to stderr
+        OutputFormat::Text | OutputFormat::Sarif => write_text_plan(plans, &mut io::stdout().lock()),
+        OutputFormat::Junit | OutputFormat::Html => write_text_plan(plans, &mut io::stderr().lock()),
+        OutputFormat::Json => {
+            println!("{}", serde_json::to_string_pretty(plans)?);
+            Ok(())
+        }
+        OutputFormat::Ndjson => plans.iter()
+            .try_for_each(|plan| write_record(&mut io::stdout().lock(), &StreamRecord::Plan(plan)))
+            .map_err(Into::into),
+        OutputFormat::Csv => write_csv_plan(plans),
+    }
+}
+
+fn line_endings_change(plan: &ConversionPlan) -> String {
+    match (plan.line_endings_before, plan.line_endings_after) {
+        (Some(before), Some(after)) if before != after => {
+            format!("{} → {}", before.as_str(), after.as_str())
+        }
+        (Some(before), _) => before.as_str().to_string(),
+        _ => String::new(),
+    }
+}
+
+fn write_text_plan(plans: &[ConversionPlan], out: &mut dyn Write) -> Result<(), Box<dyn std::error::Error>> {
+    writeln!(out, "\n=== Conversion Plan (dry run) ===")?;
+    for plan in plans {
+        writeln!(out, "📝 {} → {}", escaped_path(&plan.path), escaped_path(&plan.output_path))?;
+        let line_endings = line_endings_change(plan);
+        let line_endings = if line_endings.is_empty() {
+            String::new()
+        } else {
+            format!(", line endings {}", line_endings)
+        };
+        let text = if plan.text_changed { ", text changes" } else { "" };
+        writeln!(out, "   {} → {}, {}{}{}", plan.from, plan.to, plan.bom.as_str(), line_endings, text)?;
+        if plan.already_compliant {
+            writeln!(out, "   = already compliant, would be skipped")?;
+        }
+        if plan.unmappable_count > 0 {
+            let listed: Vec<String> = plan.unmappable.iter()
+                .map(|u| format!("'{}' at {}:{}", u.character, u.line, u.column))
+                .collect();
+            writeln!(out, "   ⚠ {} unmappable character(s): {}", plan.unmappable_count, listed.join(", "))?;
+        }
+        if let Some(error) = &plan.error {
+            writeln!(out, "   ✗ {}", error)?;
+        }
+        if let Some(diff) = &plan.diff {
+            write!(out, "{}", diff)?;
+        }
+    }
     Ok(())
 }
+
+fn write_csv_plan(plans: &[ConversionPlan]) -> Result<(), Box<dyn std::error::Error>> {
+    println!("\nConversion Plan:");
+    let mut writer = csv::Writer::from_writer(std::io::stdout());
+    writer.write_record(["Path", "Output Path", "From", "To", "BOM", "Line Endings", "Text Changed", "Already Compliant", "Unmappable", "Error"])?;
+    for plan in plans {
+        writer.write_record([
+            escaped_path(&plan.path).as_str(),
+            escaped_path(&plan.output_path).as_str(),
+            &plan.from,
+            &plan.to,
+            plan.bom.as_str(),
+            &line_endings_change(plan),
+            if plan.text_changed { "yes" } else { "no" },
+            if plan.already_compliant { "yes" } else { "no" },
+            &plan.unmappable_count.to_string(),
+            plan.error.as_deref().unwrap_or_default(),
+        ])?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+/// Escapes text for XML and HTML content and attributes. Characters XML
+/// cannot carry at all, like most control characters, become U+FFFD.
+pub fn escape_markup(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            '\t' => escaped.push_str("&#9;"),
+            '\n' => escaped.push_str("&#10;"),
+            '\r' => escaped.push_str("&#13;"),
+            c if c.is_control() && c < '\u{80}' => escaped.push('\u{FFFD}'),
+            '\u{FFFE}' | '\u{FFFF}' => escaped.push('\u{FFFD}'),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn escapes_markup_characters() {
+        assert_eq!(escape_markup(r#"<a href="x">Tom & 'Jerry'</a>"#),
+            "&lt;a href=&quot;x&quot;&gt;Tom &amp; &apos;Jerry&apos;&lt;/a&gt;");
+    }
+
+    #[test]
+    fn keeps_whitespace_as_character_references() {
+        assert_eq!(escape_markup("a\tb\r\nc"), "a&#9;b&#13;&#10;c");
+    }
+
+    #[test]
+    fn replaces_characters_xml_cannot_carry() {
+        assert_eq!(escape_markup("nul\0 bell\u{7} del\u{7F} \u{FFFE}\u{FFFF}"), "nul\u{FFFD} bell\u{FFFD} del\u{FFFD} \u{FFFD}\u{FFFD}");
+        assert_eq!(escape_markup("café 日本 \u{202E}"), "café 日本 \u{202E}");
+    }
+}
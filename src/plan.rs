@@ -0,0 +1,209 @@
+use std::path::{Path, PathBuf};
+use serde::Serialize;
+use similar::TextDiff;
+use crate::compression;
+use crate::conversion::{ConversionOptions, EncodingConverter};
+use crate::detection::{detect_line_endings, LineEndingStyle};
+use crate::filenames::{escaped_path, serialize_path};
+use crate::findings::position;
+use crate::output::FileReport;
+
+// Enough to show what is wrong without flooding the report
+const MAX_UNMAPPABLE_LISTED: usize = 10;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BomChange {
+    None,
+    Added,
+    Removed,
+    Kept,
+}
+
+impl BomChange {
+    fn new(before: bool, after: bool) -> Self {
+        match (before, after) {
+            (false, false) => BomChange::None,
+            (false, true) => BomChange::Added,
+            (true, false) => BomChange::Removed,
+            (true, true) => BomChange::Kept,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            BomChange::None => "no BOM",
+            BomChange::Added => "BOM added",
+            BomChange::Removed => "BOM removed",
+            BomChange::Kept => "BOM kept",
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct Unmappable {
+    pub line: usize,
+    pub column: usize,
+    pub character: char,
+}
+
+/// What converting one file would do, computed without writing anything.
+#[derive(Debug, Serialize)]
+pub struct ConversionPlan {
+    #[serde(serialize_with = "serialize_path")]
+    pub path: PathBuf,
+    #[serde(serialize_with = "serialize_path")]
+    pub output_path: PathBuf,
+    pub from: String,
+    pub to: String,
+    pub bom: BomChange,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub line_endings_before: Option<LineEndingStyle>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub line_endings_after: Option<LineEndingStyle>,
+    /// Whether the decoded text changes, ignoring line endings
+    pub text_changed: bool,
+    /// The output would be byte-identical to the input, so the file is skipped
+    pub already_compliant: bool,
+    pub unmappable_count: usize,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub unmappable: Vec<Unmappable>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub diff: Option<String>,
+}
+
+pub fn plan_conversion(
+    file: &FileReport,
+    to: &str,
+    options: &ConversionOptions,
+    output_dir: &Path,
+    with_diff: bool,
+) -> ConversionPlan {
+    let mut plan = ConversionPlan {
+        path: file.path.clone(),
+        output_path: output_dir.join(&file.name),
+        from: file.encoding.encoding.clone(),
+        to: to.to_string(),
+        bom: BomChange::new(file.encoding.bom.is_some(), matches!(to, "UTF-8-BOM" | "UTF-16LE" | "UTF-16BE")),
+        line_endings_before: None,
+        line_endings_after: None,
+        text_changed: false,
+        already_compliant: false,
+        unmappable_count: 0,
+        unmappable: Vec::new(),
+        error: None,
+        diff: None,
+    };
+
+    let input = match compression::read_file(&file.path, options.decompress) {
+        Ok((input, _)) => input,
+        Err(e) => {
+            plan.error = Some(format!("IO error: {}", e));
+            return plan;
+        }
+    };
+    let before = EncodingConverter::decode(&input, &file.encoding, options);
+    let after = EncodingConverter::transform(&input, &file.encoding, to, options);
+    let (before, after) = match (before, after) {
+        (Ok(before), Ok(after)) => (before, after),
+        (Err(e), _) | (_, Err(e)) => {
+            plan.error = Some(e.to_string());
+            return plan;
+        }
+    };
+
+    plan.line_endings_before = detect_line_endings(&before);
+    plan.line_endings_after = detect_line_endings(&after);
+
+    let unmappable = EncodingConverter::unmappable(&after, to).unwrap_or_default();
+    plan.unmappable_count = unmappable.len();
+    plan.unmappable = unmappable.iter()
+        .take(MAX_UNMAPPABLE_LISTED)
+        .map(|&offset| {
+            let (line, column) = position(&after, offset);
+            let character = after[offset..].chars().next().unwrap_or_default();
+            Unmappable { line, column, character }
+        })
+        .collect();
+    match EncodingConverter::encode(&after, to) {
+        Ok(output) => plan.already_compliant = output == input,
+        Err(e) => plan.error = Some(e.to_string()),
+    }
+
+    let before = before.replace("\r\n", "\n");
+    let after = after.replace("\r\n", "\n");
+    plan.text_changed = before != after;
+    if with_diff && plan.text_changed {
+        let old_header = escaped_path(&file.path);
+        let new_header = escaped_path(&plan.output_path);
+        plan.diff = Some(TextDiff::from_lines(&before, &after)
+            .unified_diff()
+            .context_radius(3)
+            .header(&old_header, &new_header)
+            .to_string());
+    }
+    plan
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::conversion::LineEnding;
+    use crate::testing::{analyze, conversion_options, TempDir};
+
+    #[test]
+    fn plans_bom_line_ending_and_declaration_changes() {
+        let dir = TempDir::new();
+        let path = dir.write("page.html", b"<meta charset=\"ISO-8859-1\">\r\ncaf\xe9\r\n");
+        let options = ConversionOptions {
+            line_ending: LineEnding::Unix,
+            rewrite_declarations: true,
+            ..conversion_options()
+        };
+        let plan = plan_conversion(&analyze(&path), "UTF-8-BOM", &options, &dir.path().join("out"), true);
+
+        assert_eq!(plan.output_path, dir.path().join("out/page.html"));
+        assert_eq!(plan.bom, BomChange::Added);
+        assert_eq!(plan.line_endings_before, Some(LineEndingStyle::Crlf));
+        assert_eq!(plan.line_endings_after, Some(LineEndingStyle::Lf));
+        assert!(plan.text_changed);
+        assert!(!plan.already_compliant);
+        assert_eq!(plan.error, None);
+        let diff = plan.diff.unwrap();
+        assert!(diff.contains("-<meta charset=\"ISO-8859-1\">\n+<meta charset=\"UTF-8\">\n"), "{}", diff);
+    }
+
+    #[test]
+    fn plans_removing_a_bom() {
+        let dir = TempDir::new();
+        let path = dir.write("a.txt", "\u{feff}caf\u{e9}\n");
+        let plan = plan_conversion(&analyze(&path), "UTF-8", &conversion_options(), dir.path(), true);
+        assert_eq!(plan.bom, BomChange::Removed);
+        assert!(!plan.text_changed);
+        assert_eq!(plan.diff, None);
+    }
+
+    #[test]
+    fn lists_unmappable_characters_where_they_are() {
+        let dir = TempDir::new();
+        let path = dir.write("a.txt", "fine\nnot \u{4e2d} or \u{6587}\n");
+        let plan = plan_conversion(&analyze(&path), "ISO-8859-1", &conversion_options(), dir.path(), false);
+        assert_eq!(plan.unmappable_count, 2);
+        let found: Vec<_> = plan.unmappable.iter().map(|u| (u.line, u.column, u.character)).collect();
+        assert_eq!(found, [(2, 5, '\u{4e2d}'), (2, 10, '\u{6587}')]);
+        assert!(plan.error.is_some());
+    }
+
+    #[test]
+    fn plans_to_skip_compliant_files() {
+        let dir = TempDir::new();
+        let path = dir.write("a.txt", "plain ascii\n");
+        let plan = plan_conversion(&analyze(&path), "UTF-8", &conversion_options(), dir.path(), true);
+        assert_eq!(plan.from, "ASCII");
+        assert!(plan.already_compliant);
+        assert!(!plan.text_changed);
+        assert_eq!(plan.bom, BomChange::None);
+    }
+}
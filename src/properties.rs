@@ -0,0 +1,163 @@
+use std::path::Path;
+
+/// Counts of `\uXXXX` escapes in a Java `.properties` file.
+#[derive(Debug, Default)]
+pub struct EscapeStats {
+    pub total: usize,
+    pub non_latin1: usize,
+}
+
+pub fn is_properties_file(path: &Path) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("properties"))
+}
+
+pub fn count_escapes(text: &str) -> EscapeStats {
+    let mut stats = EscapeStats::default();
+    let mut high_end = None;
+    for (start, end, code) in escapes(text) {
+        // The low half of a surrogate pair belongs to the preceding escape
+        if matches!(code, 0xDC00..=0xDFFF) && high_end == Some(start) {
+            continue;
+        }
+        high_end = matches!(code, 0xD800..=0xDBFF).then_some(end);
+        stats.total += 1;
+        if code > 0xFF {
+            stats.non_latin1 += 1;
+        }
+    }
+    stats
+}
+
+/// Replaces `\uXXXX` escapes of non-ASCII characters with the characters
+/// themselves. ASCII escapes are kept since they may stand for separators,
+/// comment markers or whitespace that are significant in the file format.
+pub fn unescape(text: &str) -> (String, usize) {
+    let mut result = String::with_capacity(text.len());
+    let mut last = 0;
+    let mut replaced = 0;
+    let mut pending_high: Option<(usize, usize, u32)> = None;
+
+    for (start, end, code) in escapes(text) {
+        let (start, c) = match (pending_high.take(), code) {
+            (_, 0xD800..=0xDBFF) => {
+                pending_high = Some((start, end, code));
+                continue;
+            }
+            (Some((high_start, high_end, high)), 0xDC00..=0xDFFF) if high_end == start => {
+                let combined = 0x10000 + ((high - 0xD800) << 10) + (code - 0xDC00);
+                (high_start, char::from_u32(combined))
+            }
+            (_, code) if code > 0x7F => (start, char::from_u32(code)),
+            _ => continue,
+        };
+        if let Some(c) = c {
+            result.push_str(&text[last..start]);
+            result.push(c);
+            last = end;
+            replaced += 1;
+        }
+    }
+    result.push_str(&text[last..]);
+    (result, replaced)
+}
+
+/// Escapes every character above `max` as `\uXXXX`, using surrogate pairs
+/// outside the Basic Multilingual Plane like `Properties.store` does.
+pub fn escape_above(text: &str, max: u32) -> (String, usize) {
+    let mut result = String::with_capacity(text.len());
+    let mut escaped = 0;
+    for c in text.chars() {
+        if (c as u32) <= max {
+            result.push(c);
+            continue;
+        }
+        let mut units = [0u16; 2];
+        for unit in c.encode_utf16(&mut units) {
+            result.push_str(&format!("\\u{:04X}", unit));
+        }
+        escaped += 1;
+    }
+    (result, escaped)
+}
+
+/// Yields the byte range and code unit of each `\uXXXX` escape, skipping
+/// escaped backslashes so `\\u0041` is not treated as an escape.
+fn escapes(text: &str) -> impl Iterator<Item = (usize, usize, u32)> + '_ {
+    let bytes = text.as_bytes();
+    let mut pos = 0;
+    std::iter::from_fn(move || {
+        while pos < bytes.len() {
+            if bytes[pos] != b'\\' {
+                pos += 1;
+                continue;
+            }
+            let start = pos;
+            pos += 2;
+            if bytes.get(start + 1) != Some(&b'u') {
+                continue;
+            }
+            // Java allows any number of 'u's after the backslash
+            let mut hex_start = start + 2;
+            while bytes.get(hex_start) == Some(&b'u') {
+                hex_start += 1;
+            }
+            let code = text.get(hex_start..hex_start + 4)
+                .filter(|hex| hex.bytes().all(|b| b.is_ascii_hexdigit()))
+                .and_then(|hex| u32::from_str_radix(hex, 16).ok());
+            if let Some(code) = code {
+                pos = hex_start + 4;
+                return Some((start, pos, code));
+            }
+        }
+        None
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unescapes_non_ascii_escapes_only() {
+        assert_eq!(unescape(r"greeting=Gr\u00FC\u00DFe"), ("greeting=Grüße".to_string(), 2));
+        // An escaped '=' is part of the key, so it stays escaped
+        assert_eq!(unescape(r"a\u003Db=\u00e9"), (r"a\u003Db=é".to_string(), 1));
+        // Java accepts any number of 'u's
+        assert_eq!(unescape(r"\uuu00E9"), ("é".to_string(), 1));
+    }
+
+    #[test]
+    fn unescapes_surrogate_pairs() {
+        assert_eq!(unescape(r"smile=\uD83D\uDE00!"), ("smile=😀!".to_string(), 1));
+        // Halves that do not form a pair are left alone
+        assert_eq!(unescape(r"\uD83D x \uDE00"), (r"\uD83D x \uDE00".to_string(), 0));
+    }
+
+    #[test]
+    fn skips_escaped_backslashes() {
+        assert_eq!(unescape(r"path=C:\\u00FCber"), (r"path=C:\\u00FCber".to_string(), 0));
+        assert_eq!(unescape(r"x=\\\u00FC"), (r"x=\\ü".to_string(), 1));
+    }
+
+    #[test]
+    fn escapes_characters_above_the_limit() {
+        assert_eq!(escape_above("Grüße 😀", 0xFF), (r"Grüße \uD83D\uDE00".to_string(), 1));
+        assert_eq!(escape_above("Grüße 😀", 0x7F), (r"Gr\u00FC\u00DFe \uD83D\uDE00".to_string(), 3));
+        assert_eq!(unescape(&escape_above("Grüße 😀", 0x7F).0).0, "Grüße 😀");
+    }
+
+    #[test]
+    fn counts_escapes_and_pairs_once() {
+        let stats = count_escapes(r"a=\u0041\u00E9\u4E2D\uD83D\uDE00 b=\\u4E2D");
+        assert_eq!((stats.total, stats.non_latin1), (4, 2));
+    }
+
+    #[test]
+    fn recognises_properties_files() {
+        assert!(is_properties_file(Path::new("conf/messages_de.properties")));
+        assert!(is_properties_file(Path::new("LEGACY.PROPERTIES")));
+        assert!(!is_properties_file(Path::new("properties.txt")));
+    }
+}
@@ -1,9 +1,17 @@
-use std::path::{Path, PathBuf};
-use std::fs::{self, File};
-use std::io::{self, Read};
+use std::path::{Component, Path, PathBuf};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
 use chrono::Local;
 use log::{info, warn};
-use crate::detection::{detect_encoding, FileEncoding};
+use crate::access::{ErrorKind, FileError};
+use crate::compression;
+use crate::detection::{detect_encoding_from_bytes, FileEncoding};
+use crate::filenames::escaped_path;
+use crate::metadata::{copy_metadata, file_id, PreserveOptions};
+
+/// Directory below the backup directory that files named with --files-from
+/// are backed up to, by their full path.
+const LISTED_BACKUP_DIR: &str = "listed";
 
 #[derive(Debug)]
 pub enum SafetyError {
@@ -26,21 +34,79 @@ impl std::fmt::Display for SafetyError {
 
 impl std::error::Error for SafetyError {}
 
+impl From<&SafetyError> for FileError {
+    fn from(error: &SafetyError) -> Self {
+        match error {
+            SafetyError::IoError(e) => FileError::from(e),
+            SafetyError::VerificationFailed(_) => FileError::new(ErrorKind::Verification, error.to_string()),
+            _ => FileError::new(ErrorKind::Io, error.to_string()),
+        }
+    }
+}
+
 impl From<io::Error> for SafetyError {
     fn from(error: io::Error) -> Self {
         SafetyError::IoError(error)
     }
 }
 
+fn log_dispatch() -> fern::Dispatch {
+    fern::Dispatch::new()
+        .format(|out, message, record| {
+            out.finish(format_args!(
+                "{}[{}] {}",
+                Local::now().format("[%Y-%m-%d][%H:%M:%S]"),
+                record.level(),
+                message
+            ))
+        })
+        .level(log::LevelFilter::Info)
+        .level_for("convert_rust", log::LevelFilter::Debug)
+}
+
+fn apply_logging(config: fern::Dispatch) -> Result<(), SafetyError> {
+    config.apply().map_err(|e| SafetyError::IoError(io::Error::other(
+        format!("Failed to initialize logging: {}", e)
+    )))
+}
+
+/// Sends log output to stderr only, for filter mode where stdout carries the
+/// converted data and nothing is written to disk.
+pub fn init_stderr_logging() -> Result<(), SafetyError> {
+    apply_logging(log_dispatch().chain(io::stderr()))
+}
+
 pub struct ConversionSafety {
     backup_dir: Option<PathBuf>,
     log_file: PathBuf,
-    input_dir: PathBuf,
+    rename_journal: PathBuf,
+    report_file: PathBuf,
+    input_dirs: Vec<PathBuf>,
     create_backup: bool,
+    preserve: PreserveOptions,
 }
 
 impl ConversionSafety {
-    pub fn new(input_dir: &Path, output_dir: &Path, create_backup: bool) -> Result<Self, SafetyError> {
+    pub fn new(
+        input_dirs: Vec<PathBuf>,
+        output_dir: &Path,
+        create_backup: bool,
+        preserve: PreserveOptions,
+        log_to_stderr: bool,
+    ) -> Result<Self, SafetyError> {
+        let safety = Self::without_logging(input_dirs, output_dir, create_backup, preserve)?;
+        safety.init_logging(log_to_stderr)?;
+        Ok(safety)
+    }
+
+    /// Sets up the backup directory and file names like `new`, but leaves
+    /// the process-wide logger alone.
+    pub fn without_logging(
+        input_dirs: Vec<PathBuf>,
+        output_dir: &Path,
+        create_backup: bool,
+        preserve: PreserveOptions,
+    ) -> Result<Self, SafetyError> {
         let timestamp = Local::now().format("%Y%m%d_%H%M%S");
         let backup_dir = if create_backup {
             let dir = output_dir.join(format!("backup_{}", timestamp));
@@ -51,19 +117,23 @@ impl ConversionSafety {
         };
         
         let log_file = output_dir.join(format!("conversion_log_{}.txt", timestamp));
+        let rename_journal = output_dir.join(format!("rename_journal_{}.tsv", timestamp));
+        let report_file = output_dir.join(format!("conversion_report_{}.html", timestamp));
         
-        let safety = ConversionSafety {
+        Ok(ConversionSafety {
             backup_dir,
             log_file,
-            input_dir: input_dir.to_path_buf(),
+            rename_journal,
+            report_file,
+            input_dirs,
             create_backup,
-        };
-        
-        safety.init_logging()?;
-        Ok(safety)
+            preserve,
+        })
     }
 
-    fn init_logging(&self) -> Result<(), SafetyError> {
+    /// Logs to the log file and to stdout, or to stderr when stdout is
+    /// reserved for a report.
+    fn init_logging(&self, to_stderr: bool) -> Result<(), SafetyError> {
         // Create parent directory for log file if it doesn't exist
         if let Some(parent) = self.log_file.parent() {
             fs::create_dir_all(parent)?;
@@ -71,24 +141,12 @@ impl ConversionSafety {
 
         let log_file = File::create(&self.log_file)?;
         
-        let config = fern::Dispatch::new()
-            .format(|out, message, record| {
-                out.finish(format_args!(
-                    "{}[{}] {}",
-                    Local::now().format("[%Y-%m-%d][%H:%M:%S]"),
-                    record.level(),
-                    message
-                ))
-            })
-            .chain(log_file)
-            .chain(io::stdout())
-            .level(log::LevelFilter::Info)
-            .level_for("convert_rust", log::LevelFilter::Debug);
-
-        config.apply().map_err(|e| SafetyError::IoError(io::Error::new(
-            io::ErrorKind::Other,
-            format!("Failed to initialize logging: {}", e)
-        )))?;
+        let console = if to_stderr {
+            fern::Output::from(io::stderr())
+        } else {
+            fern::Output::from(io::stdout())
+        };
+        apply_logging(log_dispatch().chain(log_file).chain(console))?;
 
         info!("Conversion process started");
         Ok(())
@@ -99,17 +157,31 @@ impl ConversionSafety {
             return Ok(None);
         }
 
-        // Get the relative path from input_dir to file_path
-        let rel_path = file_path.strip_prefix(&self.input_dir)
-            .map_err(|_| SafetyError::BackupFailed(
-                format!("File {} is not within input directory {}", 
-                    file_path.display(), self.input_dir.display())
+        // Get the relative path from its input directory to file_path
+        let rel_path = self.relative_to_input(file_path)
+            .ok_or_else(|| SafetyError::BackupFailed(
+                format!("File {} is not within an input directory", file_path.display())
             ))?;
 
+        // A backup must land inside the backup directory and never on the
+        // file itself, which fs::copy would truncate
+        if rel_path.as_os_str().is_empty()
+            || rel_path.components().any(|c| !matches!(c, Component::Normal(_)))
+        {
+            return Err(SafetyError::BackupFailed(
+                format!("Backup path for {} would be outside the backup directory", file_path.display())
+            ));
+        }
+
         // Create the backup path with preserved directory structure
         let backup_path = self.backup_dir.as_ref()
             .ok_or_else(|| SafetyError::BackupFailed("Backup directory not initialized".to_string()))?
             .join(rel_path);
+        if backup_path == file_path || file_id(&backup_path).is_some_and(|id| file_id(file_path) == Some(id)) {
+            return Err(SafetyError::BackupFailed(
+                format!("Backup of {} would overwrite the file itself", file_path.display())
+            ));
+        }
 
         // Create parent directories if they don't exist
         if let Some(parent) = backup_path.parent() {
@@ -117,16 +189,65 @@ impl ConversionSafety {
         }
 
         fs::copy(file_path, &backup_path)?;
+        self.preserve_metadata(file_path, &backup_path);
         info!("Created backup of {} at {}", file_path.display(), backup_path.display());
         Ok(Some(backup_path))
     }
 
+    /// Carries the selected metadata of `source` over to `target`. Failures
+    /// are logged but do not fail the conversion.
+    pub fn preserve_metadata(&self, source: &Path, target: &Path) {
+        if let Err(e) = copy_metadata(source, target, &self.preserve) {
+            warn!("Could not preserve metadata of {} on {}: {}", source.display(), target.display(), e);
+        }
+    }
+
+    /// Path of a file below the input directory it was found in. With
+    /// several input directories the directory's own name is kept in front,
+    /// so files with the same relative path do not collide. Files named
+    /// with --files-from, which have an empty root, can be anywhere; they
+    /// get their full canonical path below [`LISTED_BACKUP_DIR`].
+    fn relative_to_input(&self, path: &Path) -> Option<PathBuf> {
+        let scanned = self.input_dirs.iter()
+            .filter(|root| !root.as_os_str().is_empty())
+            .find(|root| path.starts_with(root));
+        let Some(root) = scanned else {
+            if !self.input_dirs.iter().any(|root| root.as_os_str().is_empty()) {
+                return None;
+            }
+            let absolute = fs::canonicalize(path).ok()?;
+            let rel_path: PathBuf = absolute.components()
+                .filter(|c| matches!(c, Component::Normal(_)))
+                .collect();
+            return Some(Path::new(LISTED_BACKUP_DIR).join(rel_path));
+        };
+        let rel_path = path.strip_prefix(root).ok()?;
+        match root.file_name() {
+            Some(name) if self.input_dirs.len() > 1 => Some(Path::new(name).join(rel_path)),
+            _ => Some(rel_path.to_path_buf()),
+        }
+    }
+
+    /// Whether `path` is a symlink that would redirect a write to a file
+    /// outside every input directory.
+    pub fn is_link_outside_input(&self, path: &Path) -> bool {
+        if !fs::symlink_metadata(path).is_ok_and(|m| m.file_type().is_symlink()) {
+            return false;
+        }
+        let Ok(target) = fs::canonicalize(path) else { return false };
+        !self.input_dirs.iter().any(|root| {
+            // An empty root stands for the current directory
+            let root = if root.as_os_str().is_empty() { Path::new(".") } else { root };
+            fs::canonicalize(root).is_ok_and(|root| target.starts_with(root))
+        })
+    }
+
     pub fn verify_conversion(&self, original: &Path, converted: &Path) -> Result<(), SafetyError> {
         info!("Verifying conversion of {}", original.display());
 
-        // Check if the converted file exists and is readable
-        let mut converted_content = Vec::new();
-        File::open(converted)?.read_to_end(&mut converted_content)?;
+        // Check if the converted file exists and is readable, looking
+        // through any compression the output was written with
+        let (converted_content, _) = compression::read_file(converted, true)?;
 
         if converted_content.is_empty() {
             return Err(SafetyError::VerificationFailed(
@@ -135,25 +256,23 @@ impl ConversionSafety {
         }
 
         // Detect encoding of converted file
-        let encoding = detect_encoding(converted);
+        let encoding = detect_encoding_from_bytes(&converted_content);
         
         // Verify the converted file is readable with its encoding
-        self.verify_file_readability(converted, &encoding)?;
+        self.verify_file_readability(converted, &converted_content, &encoding)?;
 
         info!("Conversion verification successful for {}", original.display());
         Ok(())
     }
 
-    fn verify_file_readability(&self, path: &Path, encoding: &FileEncoding) -> Result<(), SafetyError> {
-        let content = fs::read(path)?;
-        
+    fn verify_file_readability(&self, path: &Path, content: &[u8], encoding: &FileEncoding) -> Result<(), SafetyError> {
         // Try to decode the content using the detected encoding
         let decoder = encoding_rs::Encoding::for_label(encoding.encoding.as_bytes())
             .ok_or_else(|| SafetyError::VerificationFailed(
                 format!("Invalid encoding: {}", encoding.encoding)
             ))?;
 
-        let (cow, had_errors) = decoder.decode_without_bom_handling(&content);
+        let (cow, had_errors) = decoder.decode_without_bom_handling(content);
         
         if had_errors {
             return Err(SafetyError::VerificationFailed(
@@ -193,6 +312,22 @@ impl ConversionSafety {
         Ok(())
     }
 
+    /// Appends a rename to the journal so it can be reverted later. Paths are
+    /// written with undecodable bytes escaped as \xNN.
+    pub fn record_rename(&self, from: &Path, to: &Path) -> Result<(), SafetyError> {
+        let mut journal = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.rename_journal)?;
+        writeln!(journal, "{}\t{}", escaped_path(from), escaped_path(to))?;
+        info!("Renamed {} to {}", escaped_path(from), escaped_path(to));
+        Ok(())
+    }
+
+    pub fn get_rename_journal(&self) -> Option<&Path> {
+        Some(self.rename_journal.as_path()).filter(|p| p.exists())
+    }
+
     pub fn get_backup_dir(&self) -> Option<&Path> {
         self.backup_dir.as_deref()
     }
@@ -200,4 +335,67 @@ impl ConversionSafety {
     pub fn get_log_file(&self) -> &Path {
         &self.log_file
     }
+
+    /// Where the HTML report of this run goes unless --report-file says otherwise.
+    pub fn get_report_file(&self) -> &Path {
+        &self.report_file
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TempDir;
+
+    fn safety(input_dirs: Vec<PathBuf>, backup_dir: &Path) -> ConversionSafety {
+        ConversionSafety {
+            backup_dir: Some(backup_dir.to_path_buf()),
+            log_file: backup_dir.join("log.txt"),
+            rename_journal: backup_dir.join("journal.tsv"),
+            report_file: backup_dir.join("report.html"),
+            input_dirs,
+            create_backup: true,
+            preserve: PreserveOptions { mode: true, times: true, ownership: false, xattrs: false },
+        }
+    }
+
+    #[test]
+    fn backs_up_scanned_files_below_their_root() {
+        let dir = TempDir::new();
+        let file = dir.write("in/sub/a.txt", "text");
+        let safety = safety(vec![dir.path().join("in")], &dir.path().join("backup"));
+        let backup = safety.create_backup(&file).unwrap().unwrap();
+        assert_eq!(backup, dir.path().join("backup/sub/a.txt"));
+        assert_eq!(fs::read_to_string(&backup).unwrap(), "text");
+    }
+
+    #[test]
+    fn backs_up_listed_files_by_their_full_path() {
+        // --files-from adds an empty root, which every path starts with
+        let dir = TempDir::new();
+        let file = dir.write("in/a.txt", "text");
+        let backup_dir = dir.path().join("backup");
+        let safety = safety(vec![PathBuf::new()], &backup_dir);
+
+        let backup = safety.create_backup(&file).unwrap().unwrap();
+        assert!(backup.starts_with(backup_dir.join(LISTED_BACKUP_DIR)));
+        assert!(backup.ends_with("in/a.txt"));
+        assert_eq!(fs::read_to_string(&file).unwrap(), "text");
+        assert_eq!(fs::read_to_string(&backup).unwrap(), "text");
+
+        // Parent components are resolved instead of escaping the backup directory
+        let dotted = dir.path().join("in/../in/a.txt");
+        let backup = safety.create_backup(&dotted).unwrap().unwrap();
+        assert!(backup.starts_with(backup_dir.join(LISTED_BACKUP_DIR)));
+        assert!(!backup.components().any(|c| c == Component::ParentDir));
+    }
+
+    #[test]
+    fn never_backs_up_a_file_onto_itself() {
+        let dir = TempDir::new();
+        let file = dir.write("in/a.txt", "text");
+        let safety = safety(vec![dir.path().join("in")], &dir.path().join("in"));
+        assert!(matches!(safety.create_backup(&file), Err(SafetyError::BackupFailed(_))));
+        assert_eq!(fs::read_to_string(&file).unwrap(), "text");
+    }
 }
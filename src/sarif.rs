@@ -0,0 +1,210 @@
+use std::path::Path;
+use serde::Serialize;
+use crate::detection::LineEndingStyle;
+use crate::filenames::path_uri;
+use crate::findings::Severity;
+use crate::output::ScanReport;
+
+const SARIF_VERSION: &str = "2.1.0";
+const SARIF_SCHEMA: &str = "https://json.schemastore.org/sarif-2.1.0.json";
+
+#[derive(Serialize)]
+struct SarifLog {
+    #[serde(rename = "$schema")]
+    schema: &'static str,
+    version: &'static str,
+    runs: Vec<Run>,
+}
+
+#[derive(Serialize)]
+struct Run {
+    tool: Tool,
+    results: Vec<SarifResult>,
+}
+
+#[derive(Serialize)]
+struct Tool {
+    driver: Driver,
+}
+
+#[derive(Serialize)]
+struct Driver {
+    name: &'static str,
+    version: &'static str,
+    rules: Vec<Rule>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Rule {
+    id: String,
+    short_description: Message,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SarifResult {
+    rule_id: String,
+    rule_index: usize,
+    level: &'static str,
+    message: Message,
+    locations: Vec<Location>,
+}
+
+#[derive(Serialize)]
+struct Message {
+    text: String,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Location {
+    physical_location: PhysicalLocation,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct PhysicalLocation {
+    artifact_location: ArtifactLocation,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    region: Option<Region>,
+}
+
+#[derive(Serialize)]
+struct ArtifactLocation {
+    uri: String,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Region {
+    start_line: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    start_column: Option<usize>,
+}
+
+/// One-line description of a rule for SARIF viewers. Kinds without an
+/// entry are described by their ID.
+fn describe_rule(rule_id: &str) -> String {
+    let description = match rule_id {
+        "encoding-policy" => "File is not in an expected encoding",
+        "mixed-line-endings" => "File mixes different line endings",
+        "declaration-mismatch" => "Declared encoding differs from the content",
+        "unknown-declaration" => "Declared encoding is not recognised",
+        "mojibake" => "Text looks double-encoded",
+        "embedded-bom" => "Byte order mark inside the text",
+        "nul-run" => "Run of NUL bytes in text",
+        "truncated-sequence" => "File ends in the middle of a character",
+        "bidi-control" => "Bidirectional control character",
+        "zero-width" => "Invisible character",
+        "control-character" => "Unexpected control character",
+        "confusable-identifier" => "Identifier mixes Latin letters with lookalikes",
+        _ => {
+            let text = rule_id.replace('-', " ");
+            let mut chars = text.chars();
+            return chars.next().map_or(String::new(), |first| first.to_uppercase().chain(chars).collect());
+        }
+    };
+    description.to_string()
+}
+
+fn level(severity: Severity) -> &'static str {
+    match severity {
+        Severity::Info => "note",
+        Severity::Warning => "warning",
+        Severity::Error => "error",
+    }
+}
+
+/// Collects results and the rules they refer to, in order of first use.
+#[derive(Default)]
+struct Results {
+    rules: Vec<Rule>,
+    results: Vec<SarifResult>,
+}
+
+impl Results {
+    fn add(&mut self, rule_id: &str, level: &'static str, message: String, path: &Path, position: Option<(usize, Option<usize>)>) {
+        let rule_index = match self.rules.iter().position(|r| r.id == rule_id) {
+            Some(index) => index,
+            None => {
+                self.rules.push(Rule {
+                    id: rule_id.to_string(),
+                    short_description: Message { text: describe_rule(rule_id) },
+                });
+                self.rules.len() - 1
+            }
+        };
+        self.results.push(SarifResult {
+            rule_id: rule_id.to_string(),
+            rule_index,
+            level,
+            message: Message { text: message },
+            locations: vec![Location {
+                physical_location: PhysicalLocation {
+                    artifact_location: ArtifactLocation { uri: path_uri(path) },
+                    region: position.map(|(start_line, start_column)| Region { start_line, start_column }),
+                },
+            }],
+        });
+    }
+}
+
+/// Writes the findings, mixed line endings and read errors of a scan as a
+/// SARIF 2.1.0 log. Rule IDs are the finding kinds and error kinds used in
+/// the other formats; encoding policy violations use `encoding-policy`.
+pub fn write_sarif_output(report: &ScanReport) -> Result<(), Box<dyn std::error::Error>> {
+    let mut results = Results::default();
+    for file in &report.files {
+        let path = file.root.as_deref().map_or(file.path.clone(), |root| root.join(&file.path));
+        if let Some(error) = &file.error {
+            results.add(error.kind.as_str(), "error", error.message.clone(), &path, None);
+        }
+        if file.line_endings == Some(LineEndingStyle::Mixed) {
+            results.add("mixed-line-endings", "warning", "File mixes different line endings".to_string(), &path, None);
+        }
+        for finding in &file.findings {
+            let position = finding.line.map(|line| (line, finding.column));
+            results.add(finding.kind, level(finding.severity), finding.message.clone(), &path, position);
+        }
+    }
+    for error in &report.errors {
+        results.add(error.error.kind.as_str(), "error", error.error.message.clone(), &error.path, None);
+    }
+
+    let log = SarifLog {
+        schema: SARIF_SCHEMA,
+        version: SARIF_VERSION,
+        runs: vec![Run {
+            tool: Tool {
+                driver: Driver {
+                    name: env!("CARGO_PKG_NAME"),
+                    version: env!("CARGO_PKG_VERSION"),
+                    rules: results.rules,
+                },
+            },
+            results: results.results,
+        }],
+    };
+    println!("{}", serde_json::to_string_pretty(&log)?);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rules_are_described_and_shared_by_results() {
+        let mut results = Results::default();
+        results.add("encoding-policy", "error", "Content is windows-1252".to_string(), Path::new("a.txt"), None);
+        results.add("symlink-loop", "error", "Links back".to_string(), Path::new("b"), Some((3, Some(4))));
+        results.add("encoding-policy", "error", "Content is Big5".to_string(), Path::new("c.txt"), None);
+
+        let rules = serde_json::to_value(&results.rules).unwrap();
+        assert_eq!(rules[0]["shortDescription"]["text"], "File is not in an expected encoding");
+        assert_eq!(rules[1]["shortDescription"]["text"], "Symlink loop");
+        assert_eq!(results.rules.len(), 2);
+        assert_eq!(results.results[2].rule_index, 0);
+    }
+}
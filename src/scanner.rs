@@ -0,0 +1,327 @@
+use std::collections::HashSet;
+use std::fs::{self, Metadata};
+use std::io;
+use std::path::{Path, PathBuf};
+use crate::access::{ErrorKind, FileError};
+use crate::analysis::{analyze_file, AnalysisOptions};
+use crate::archive::{self, ArchiveKind};
+use crate::filenames::check_name;
+use crate::filter::FileFilter;
+use crate::statistics::Statistics;
+
+#[derive(Debug, Clone, Copy)]
+pub struct ScanOptions {
+    /// Descend into symlinked directories and read symlinked files
+    pub follow_symlinks: bool,
+}
+
+/// Walks a directory tree, analyzing every regular file the filter accepts.
+/// Directories are identified by their canonical path so symlink cycles
+/// are reported instead of followed forever, and a directory reachable
+/// through several links is only scanned once.
+pub struct Scanner<'a> {
+    stats: &'a mut Statistics,
+    filter: &'a FileFilter,
+    analysis: &'a AnalysisOptions<'a>,
+    options: ScanOptions,
+    visited: HashSet<PathBuf>,
+    ancestors: Vec<PathBuf>,
+}
+
+impl<'a> Scanner<'a> {
+    pub fn new(
+        stats: &'a mut Statistics,
+        filter: &'a FileFilter,
+        analysis: &'a AnalysisOptions<'a>,
+        options: ScanOptions,
+    ) -> Self {
+        Scanner {
+            stats,
+            filter,
+            analysis,
+            options,
+            visited: HashSet::new(),
+            ancestors: Vec::new(),
+        }
+    }
+
+    /// Scans a root path. Anything unreadable below it is recorded and
+    /// skipped; an unreadable root is recorded and also returned, so the
+    /// caller can go on with the other roots. Reported paths are relative
+    /// to the root, or to its directory if it is a file.
+    pub fn scan(&mut self, root: &Path) -> io::Result<()> {
+        self.stats.set_root(Some(root_dir(root)));
+        // A root named on the command line is always followed
+        let scanned = fs::metadata(root).and_then(|metadata| self.scan_root(root, &metadata));
+        if let Err(e) = &scanned {
+            self.stats.add_error(root, e);
+        }
+        scanned
+    }
+
+    /// Scans a path from a --files-from list. Its path is reported as given,
+    /// and errors are recorded instead of ending the scan.
+    pub fn scan_listed(&mut self, path: &Path) {
+        self.stats.set_root(None);
+        if let Err(e) = fs::metadata(path).and_then(|metadata| self.scan_root(path, &metadata)) {
+            self.stats.add_error(path, &e);
+        }
+    }
+
+    fn scan_root(&mut self, root: &Path, metadata: &Metadata) -> io::Result<()> {
+        if self.stats.output_closed() {
+            return Ok(());
+        }
+        if metadata.is_dir() {
+            self.scan_dir(root, 0)
+        } else {
+            self.scan_file(root, metadata, 0);
+            Ok(())
+        }
+    }
+
+    fn scan_dir(&mut self, dir: &Path, depth: usize) -> io::Result<()> {
+        let canonical = fs::canonicalize(dir)?;
+        if self.ancestors.contains(&canonical) {
+            self.stats.add_scan_error(dir, FileError::new(
+                ErrorKind::SymlinkLoop,
+                format!("Links back to {}; not following it", canonical.display()),
+            ));
+            return Ok(());
+        }
+        if !self.visited.insert(canonical.clone()) {
+            return Ok(());
+        }
+
+        let entries = fs::read_dir(dir)?;
+        self.ancestors.push(canonical);
+        for entry in entries {
+            if self.stats.output_closed() {
+                break;
+            }
+            let path = match entry {
+                Ok(entry) => entry.path(),
+                Err(e) => {
+                    self.stats.add_error(dir, &e);
+                    continue;
+                }
+            };
+            let metadata = match self.metadata(&path) {
+                Ok(Some(metadata)) => metadata,
+                Ok(None) => continue,
+                Err(e) => {
+                    self.stats.add_error(&path, &e);
+                    continue;
+                }
+            };
+            if metadata.is_dir() {
+                // Pruned directories are not read at all
+                if !self.filter.should_enter(&path, depth + 1) {
+                    continue;
+                }
+                self.check_name(&path);
+                if let Err(e) = self.scan_dir(&path, depth + 1) {
+                    self.stats.add_error(&path, &e);
+                }
+            } else {
+                self.scan_file(&path, &metadata, depth + 1);
+            }
+        }
+        self.ancestors.pop();
+        Ok(())
+    }
+
+    /// Metadata of a directory entry, or None for symlinks that are not
+    /// followed and links whose target does not exist.
+    fn metadata(&self, path: &Path) -> io::Result<Option<Metadata>> {
+        let metadata = fs::symlink_metadata(path)?;
+        if !metadata.file_type().is_symlink() {
+            return Ok(Some(metadata));
+        }
+        if !self.options.follow_symlinks {
+            return Ok(None);
+        }
+        match fs::metadata(path) {
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+            result => result.map(Some),
+        }
+    }
+
+    fn scan_file(&mut self, path: &Path, metadata: &Metadata, depth: usize) {
+        // Reading FIFOs, sockets and devices can block or never end
+        if !metadata.is_file() {
+            return;
+        }
+        let included = self.filter.should_include_file(path, metadata, depth);
+        if included && depth > 0 {
+            self.check_name(path);
+        }
+        let archive = ArchiveKind::from_path(path)
+            .filter(|_| self.analysis.scan_archives && self.filter.matches(path, metadata, depth));
+        if let Some(kind) = archive {
+            // Entries are filtered individually, whatever the archive is called
+            if let Err(e) = archive::scan_archive(path, kind, self.stats, self.filter, self.analysis) {
+                let error = match e.downcast_ref::<io::Error>() {
+                    Some(e) => FileError::from(e),
+                    None => FileError::new(ErrorKind::Io, format!("Could not read archive: {}", e)),
+                };
+                self.stats.add_scan_error(path, error);
+            }
+        } else if included {
+            let name = path.file_name()
+                .map(|n| n.to_string_lossy().to_string())
+                .unwrap_or_else(|| path.to_string_lossy().to_string());
+            self.stats.add_file(analyze_file(path, name, self.analysis));
+        }
+    }
+
+    fn check_name(&mut self, path: &Path) {
+        if let Some(issue) = check_name(path, self.analysis.nfc_filenames) {
+            self.stats.add_filename_issue(issue);
+        }
+    }
+}
+
+/// Directory that paths below `root` are reported relative to: the root
+/// itself, or the directory containing it when it names a file.
+pub fn root_dir(root: &Path) -> PathBuf {
+    if root.is_dir() {
+        root.to_path_buf()
+    } else {
+        root.parent().map(Path::to_path_buf).unwrap_or_default()
+    }
+}
+
+/// Splits a --files-from list into paths. The list is NUL-separated if it
+/// contains a NUL byte (`find -print0`, `git ls-files -z`), otherwise one
+/// path per line.
+pub fn parse_path_list(content: &[u8]) -> Vec<PathBuf> {
+    let separator = if content.contains(&0) { 0 } else { b'\n' };
+    content.split(|&b| b == separator)
+        .map(|entry| if separator == b'\n' { entry.strip_suffix(b"\r").unwrap_or(entry) } else { entry })
+        .filter(|entry| !entry.is_empty())
+        .map(path_from_bytes)
+        .collect()
+}
+
+#[cfg(unix)]
+fn path_from_bytes(bytes: &[u8]) -> PathBuf {
+    use std::os::unix::ffi::OsStrExt;
+    PathBuf::from(std::ffi::OsStr::from_bytes(bytes))
+}
+
+#[cfg(not(unix))]
+fn path_from_bytes(bytes: &[u8]) -> PathBuf {
+    PathBuf::from(String::from_utf8_lossy(bytes).into_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{Config, SourceOverrides};
+    use crate::output::ScanReport;
+    use crate::testing::TempDir;
+
+    /// Scans `root` and returns the report with its paths relative to it.
+    fn scan(root: &Path, follow_symlinks: bool) -> ScanReport {
+        let overrides = SourceOverrides::new(&[], None, &Config::default()).unwrap();
+        let analysis = AnalysisOptions {
+            trust_declarations: false,
+            security_scan: false,
+            nfc_filenames: false,
+            scan_archives: false,
+            decompress: false,
+            overrides: &overrides,
+            expected_encodings: &[],
+            target_encoding: None,
+        };
+        let mut stats = Statistics::new();
+        let filter = FileFilter::new(None);
+        Scanner::new(&mut stats, &filter, &analysis, ScanOptions { follow_symlinks }).scan(root).unwrap();
+        stats.generate_report()
+    }
+
+    fn scanned_paths(report: &ScanReport) -> Vec<PathBuf> {
+        let mut paths: Vec<PathBuf> = report.files.iter().map(|f| f.path.clone()).collect();
+        paths.sort();
+        paths
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn reports_symlink_loops_instead_of_following_them() {
+        let dir = TempDir::new();
+        dir.write("in/a.txt", "a");
+        dir.write("in/sub/b.txt", "b");
+        std::os::unix::fs::symlink("..", dir.path().join("in/sub/back")).unwrap();
+
+        let report = scan(&dir.path().join("in"), true);
+        assert_eq!(scanned_paths(&report), [PathBuf::from("a.txt"), PathBuf::from("sub/b.txt")]);
+        assert_eq!(report.errors.len(), 1);
+        assert_eq!(report.errors[0].path, dir.path().join("in/sub/back"));
+        assert_eq!(report.errors[0].error.kind, ErrorKind::SymlinkLoop);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn follows_symlinks_only_when_asked() {
+        let dir = TempDir::new();
+        dir.write("in/a.txt", "a");
+        dir.write("elsewhere/c.txt", "c");
+        std::os::unix::fs::symlink("a.txt", dir.path().join("in/link.txt")).unwrap();
+        std::os::unix::fs::symlink("../elsewhere", dir.path().join("in/linked")).unwrap();
+        std::os::unix::fs::symlink("missing.txt", dir.path().join("in/dangling.txt")).unwrap();
+
+        assert_eq!(scanned_paths(&scan(&dir.path().join("in"), false)), [PathBuf::from("a.txt")]);
+        let report = scan(&dir.path().join("in"), true);
+        assert_eq!(scanned_paths(&report), [
+            PathBuf::from("a.txt"),
+            PathBuf::from("link.txt"),
+            PathBuf::from("linked/c.txt"),
+        ]);
+        assert!(report.errors.is_empty());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn skips_special_files() {
+        let dir = TempDir::new();
+        dir.write("in/a.txt", "a");
+        let _socket = std::os::unix::net::UnixListener::bind(dir.path().join("in/app.sock")).unwrap();
+
+        let report = scan(&dir.path().join("in"), true);
+        assert_eq!(scanned_paths(&report), [PathBuf::from("a.txt")]);
+        assert!(report.errors.is_empty());
+    }
+
+    #[test]
+    fn parses_one_path_per_line() {
+        let paths = parse_path_list(b"src/main.rs\r\ndocs/read me.txt\n\nlast.txt");
+        assert_eq!(paths, [
+            PathBuf::from("src/main.rs"),
+            PathBuf::from("docs/read me.txt"),
+            PathBuf::from("last.txt"),
+        ]);
+    }
+
+    #[test]
+    fn parses_nul_separated_paths() {
+        // Newlines and carriage returns are part of the name once NULs are used
+        let paths = parse_path_list(b"a\nb.txt\0c\r\0\0");
+        assert_eq!(paths, [PathBuf::from("a\nb.txt"), PathBuf::from("c\r")]);
+    }
+
+    #[test]
+    fn empty_list_has_no_paths() {
+        assert!(parse_path_list(b"").is_empty());
+        assert!(parse_path_list(b"\n\n").is_empty());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn keeps_non_utf8_bytes() {
+        use std::os::unix::ffi::OsStrExt;
+        let paths = parse_path_list(b"caf\xe9.txt\n");
+        assert_eq!(paths[0].as_os_str().as_bytes(), b"caf\xe9.txt");
+    }
+}
@@ -0,0 +1,178 @@
+use crate::findings::{Finding, Severity};
+use crate::output::FileReport;
+
+/// Finding kinds that fail check mode. Embedded BOMs are found by the
+/// anomaly detection that runs on every scan, and only in UTF-8 and UTF-16.
+pub const SECURITY_KINDS: &[&str] = &[
+    "bidi-control",
+    "zero-width",
+    "control-character",
+    "confusable-identifier",
+    "embedded-bom",
+];
+
+/// Number of findings in `files` that make check mode fail.
+pub fn count_check_failures(files: &[FileReport]) -> usize {
+    files.iter()
+        .flat_map(|f| &f.findings)
+        .filter(|f| SECURITY_KINDS.contains(&f.kind))
+        .count()
+}
+
+// Latin lookalikes from the Cyrillic and Greek blocks
+const CONFUSABLES: &[char] = &[
+    'а', 'в', 'е', 'к', 'м', 'н', 'о', 'р', 'с', 'т', 'у', 'х', 'ѕ', 'і', 'ј', 'ԁ', 'ԛ', 'ԝ',
+    'А', 'В', 'Е', 'К', 'М', 'Н', 'О', 'Р', 'С', 'Т', 'Х', 'Ѕ', 'І', 'Ј',
+    'α', 'ο', 'ρ', 'ν', 'ι', 'κ', 'τ', 'υ',
+    'Α', 'Β', 'Ε', 'Ζ', 'Η', 'Ι', 'Κ', 'Μ', 'Ν', 'Ο', 'Ρ', 'Τ', 'Υ', 'Χ',
+];
+
+/// Reports characters that make source code read differently from how it
+/// is compiled: bidirectional controls (CVE-2021-42574), invisible
+/// characters, control characters and mixed-script identifiers.
+/// NUL bytes are left to the anomaly detection.
+pub fn scan_text(text: &str) -> Vec<Finding> {
+    let mut findings = Vec::new();
+    for (index, line) in text.split('\n').enumerate() {
+        let line_no = index + 1;
+        for (column, c) in line.chars().enumerate() {
+            let at = (line_no, column + 1);
+            if let Some(finding) = check_char(c) {
+                findings.push(finding.at(at));
+            }
+        }
+        findings.extend(check_identifiers(line, line_no));
+    }
+    findings
+}
+
+fn check_char(c: char) -> Option<Finding> {
+    let code = c as u32;
+    let (kind, severity, what) = match c {
+        '\u{202A}'..='\u{202E}' | '\u{2066}'..='\u{2069}' => {
+            ("bidi-control", Severity::Error, "bidirectional override/isolate")
+        }
+        '\u{200E}' | '\u{200F}' | '\u{061C}' => ("bidi-control", Severity::Warning, "bidirectional mark"),
+        '\u{200B}' | '\u{200C}' | '\u{200D}' | '\u{2060}' | '\u{180E}' | '\u{00AD}' => {
+            ("zero-width", Severity::Warning, "invisible character")
+        }
+        // Tab, line feed, form feed and carriage return are ordinary whitespace
+        '\t' | '\n' | '\x0C' | '\r' => return None,
+        '\0' => return None,
+        '\x01'..='\x1F' | '\x7F' => ("control-character", Severity::Warning, "C0 control character"),
+        '\u{80}'..='\u{9F}' => ("control-character", Severity::Warning, "C1 control character"),
+        _ => return None,
+    };
+    Some(Finding::new(kind, severity, format!("{} U+{:04X}", what, code)))
+}
+
+fn check_identifiers(line: &str, line_no: usize) -> Vec<Finding> {
+    let mut findings = Vec::new();
+    let mut start = None;
+    // Trailing sentinel flushes an identifier that ends the line
+    for (offset, c) in line.char_indices().chain(std::iter::once((line.len(), ' '))) {
+        if c.is_alphanumeric() || c == '_' {
+            start.get_or_insert(offset);
+            continue;
+        }
+        let Some(begin) = start.take() else { continue };
+        let ident = &line[begin..offset];
+        let has_latin = ident.chars().any(|c| c.is_ascii_alphabetic());
+        if let Some(lookalike) = ident.chars().find(|c| CONFUSABLES.contains(c)).filter(|_| has_latin) {
+            let column = line[..begin].chars().count() + 1;
+            findings.push(Finding::new(
+                "confusable-identifier",
+                Severity::Warning,
+                format!(
+                    "identifier '{}' mixes Latin letters with lookalike U+{:04X}",
+                    ident, lookalike as u32
+                ),
+            ).at((line_no, column)));
+        }
+    }
+    findings
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+    use crate::analysis::{analyze_bytes, AnalysisOptions};
+    use crate::config::{Config, SourceOverrides};
+
+    fn check_failures(content: &[u8]) -> usize {
+        let overrides = SourceOverrides::new(&[], None, &Config::default()).unwrap();
+        let options = AnalysisOptions {
+            trust_declarations: false,
+            security_scan: true,
+            nfc_filenames: false,
+            scan_archives: false,
+            decompress: false,
+            overrides: &overrides,
+            expected_encodings: &[],
+            target_encoding: None,
+        };
+        let report = analyze_bytes(PathBuf::from("a.txt"), "a.txt".to_string(), content, &options);
+        count_check_failures(&[report])
+    }
+
+    fn found(text: &str) -> Vec<(&'static str, Severity, usize, usize)> {
+        scan_text(text).into_iter()
+            .map(|f| (f.kind, f.severity, f.line.unwrap(), f.column.unwrap()))
+            .collect()
+    }
+
+    #[test]
+    fn reports_bidi_controls() {
+        assert_eq!(found("let x = \"\u{202E}abc\";"), [("bidi-control", Severity::Error, 1, 10)]);
+        assert_eq!(found("ok\n\u{2066}isolate\u{2069}"), [
+            ("bidi-control", Severity::Error, 2, 1),
+            ("bidi-control", Severity::Error, 2, 9),
+        ]);
+        assert_eq!(found("mark\u{200F}"), [("bidi-control", Severity::Warning, 1, 5)]);
+    }
+
+    #[test]
+    fn reports_zero_width_characters() {
+        assert_eq!(found("first\nad\u{200B}min"), [("zero-width", Severity::Warning, 2, 3)]);
+        assert_eq!(found("soft\u{AD}hyphen"), [("zero-width", Severity::Warning, 1, 5)]);
+    }
+
+    #[test]
+    fn reports_control_characters_but_not_whitespace() {
+        assert_eq!(found("bell\x07\tand\u{85}next"), [
+            ("control-character", Severity::Warning, 1, 5),
+            ("control-character", Severity::Warning, 1, 10),
+        ]);
+        let messages: Vec<String> = scan_text("\x1B[0m\u{9B}").into_iter().map(|f| f.message).collect();
+        assert_eq!(messages, ["C0 control character U+001B", "C1 control character U+009B"]);
+        assert!(found("tab\tform\x0Cfeed\r\nnul\0").is_empty());
+    }
+
+    #[test]
+    fn reports_confusable_identifiers_where_they_start() {
+        // Cyrillic а in the middle of a Latin identifier
+        let findings = scan_text("fn main() {\n    let p\u{430}ypal = 1;\n}");
+        assert_eq!(findings.len(), 1);
+        assert_eq!((findings[0].kind, findings[0].line, findings[0].column), ("confusable-identifier", Some(2), Some(9)));
+        assert!(findings[0].message.contains("U+0430"));
+
+        // Words written entirely in one script are fine
+        assert!(found("let \u{43c}\u{438}\u{440} = \"\u{3b1}\u{3b2}\";").is_empty());
+    }
+
+    #[test]
+    fn check_fails_on_security_findings_only() {
+        assert_eq!(check_failures("x = \"\u{202E}\u{200B}\"\n".as_bytes()), 2);
+        assert_eq!(check_failures(b"bell\x07\n"), 1);
+        // A NUL run is damage, not a security issue
+        assert_eq!(check_failures(b"text\0\0\0more\n"), 0);
+        assert_eq!(check_failures(b"plain ascii\n"), 0);
+    }
+
+    #[test]
+    fn check_fails_on_embedded_bom_in_utf8() {
+        assert_eq!(check_failures("first line\n\u{FEFF}second line\n".as_bytes()), 1);
+        assert_eq!(check_failures("\u{FEFF}first line\nsecond line\n".as_bytes()), 0);
+    }
+}
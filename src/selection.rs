@@ -0,0 +1,121 @@
+use crate::detection::LineEndingStyle;
+use crate::output::FileReport;
+
+/// A condition on what analysis found in a file. Files that fail a selector
+/// are left out of the report and of conversion.
+#[derive(Debug, Clone)]
+pub enum Selector {
+    /// Detected encoding names, as shown in the report (case-insensitive)
+    Encoding(Vec<String>),
+    Bom(bool),
+    /// Line-ending styles; None selects files without line breaks
+    LineEndings(Vec<Option<LineEndingStyle>>),
+    MinConfidence(f64),
+    MaxConfidence(f64),
+}
+
+impl Selector {
+    fn matches(&self, file: &FileReport) -> bool {
+        match self {
+            Selector::Encoding(names) => names.iter()
+                .any(|name| name.eq_ignore_ascii_case(&file.encoding.encoding)),
+            Selector::Bom(present) => file.encoding.bom.is_some() == *present,
+            Selector::LineEndings(styles) => styles.contains(&file.line_endings),
+            Selector::MinConfidence(min) => file.confidence >= *min,
+            Selector::MaxConfidence(max) => file.confidence <= *max,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Selection {
+    selectors: Vec<Selector>,
+}
+
+impl Selection {
+    pub fn with(mut self, selector: Selector) -> Self {
+        self.selectors.push(selector);
+        self
+    }
+
+    pub fn matches(&self, file: &FileReport) -> bool {
+        self.selectors.iter().all(|s| s.matches(file))
+    }
+}
+
+/// Parses a comma-separated list of line-ending styles (lf, crlf, cr, mixed, none).
+pub fn parse_line_endings(list: &[String]) -> Option<Vec<Option<LineEndingStyle>>> {
+    list.iter()
+        .map(|s| match s.trim().to_lowercase().as_str() {
+            "none" => Some(None),
+            other => LineEndingStyle::from_str(other).map(Some),
+        })
+        .collect()
+}
+
+/// Parses a confidence given as a fraction (0.8) or a percentage (80%).
+pub fn parse_confidence(s: &str) -> Option<f64> {
+    let value = match s.trim().strip_suffix('%') {
+        Some(percent) => percent.trim().parse::<f64>().ok()? / 100.0,
+        None => s.trim().parse::<f64>().ok()?,
+    };
+    Some(value).filter(|v| (0.0..=1.0).contains(v))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{analyze, TempDir};
+
+    /// Names of the files `selector` selects among a few typical ones.
+    fn selected(selector: Selector) -> Vec<String> {
+        let dir = TempDir::new();
+        let files = [
+            analyze(&dir.write("bom.txt", "\u{feff}caf\u{e9}\r\n")),
+            analyze(&dir.write("latin.txt", b"caf\xe9 na\xefve\n")),
+            analyze(&dir.write("plain.txt", "no line break")),
+        ];
+        let selection = Selection::default().with(selector);
+        files.into_iter().filter(|f| selection.matches(f)).map(|f| f.name).collect()
+    }
+
+    #[test]
+    fn selects_by_encoding_and_bom() {
+        assert_eq!(selected(Selector::Encoding(vec!["utf-8".to_string(), "ascii".to_string()])), ["bom.txt", "plain.txt"]);
+        assert_eq!(selected(Selector::Bom(true)), ["bom.txt"]);
+        assert_eq!(selected(Selector::Bom(false)), ["latin.txt", "plain.txt"]);
+    }
+
+    #[test]
+    fn selects_by_line_endings() {
+        assert_eq!(selected(Selector::LineEndings(vec![Some(LineEndingStyle::Crlf)])), ["bom.txt"]);
+        assert_eq!(selected(Selector::LineEndings(parse_line_endings(&["lf".to_string(), "none".to_string()]).unwrap())), ["latin.txt", "plain.txt"]);
+        assert!(parse_line_endings(&["lf".to_string(), "dos".to_string()]).is_none());
+    }
+
+    #[test]
+    fn selects_by_confidence() {
+        // BOMs and ASCII are certain, legacy detection is not
+        assert_eq!(selected(Selector::MinConfidence(1.0)), ["bom.txt", "plain.txt"]);
+        assert_eq!(selected(Selector::MaxConfidence(0.99)), ["latin.txt"]);
+    }
+
+    #[test]
+    fn selectors_combine() {
+        let dir = TempDir::new();
+        let file = analyze(&dir.write("bom.txt", "\u{feff}caf\u{e9}\r\n"));
+        let selection = Selection::default().with(Selector::Bom(true));
+        assert!(selection.matches(&file));
+        assert!(!selection.with(Selector::LineEndings(vec![Some(LineEndingStyle::Lf)])).matches(&file));
+    }
+
+    #[test]
+    fn parses_confidence_as_fraction_or_percentage() {
+        assert_eq!(parse_confidence("0.8"), Some(0.8));
+        assert_eq!(parse_confidence(" 80 % "), Some(0.8));
+        assert_eq!(parse_confidence("100%"), Some(1.0));
+        assert_eq!(parse_confidence("1.5"), None);
+        assert_eq!(parse_confidence("-5%"), None);
+        assert_eq!(parse_confidence("high"), None);
+    }
+}
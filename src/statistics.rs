@@ -1,12 +1,25 @@
 use std::collections::HashMap;
-use std::path::PathBuf;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use crate::access::{FileError, ScanError};
 use crate::detection::FileEncoding;
-use crate::output::{FileReport, ScanReport, EncodingStat, OutputFormat, write_output};
+use crate::filenames::FilenameIssue;
+use crate::selection::Selection;
+use crate::output::{FileReport, ScanReport, EncodingStat, OutputFormat, StreamRecord, write_output, write_record};
 
 pub struct Statistics {
     total_files: usize,
     encoding_counts: HashMap<FileEncoding, usize>,
     files: Vec<FileReport>,
+    filename_issues: Vec<FilenameIssue>,
+    errors: Vec<ScanError>,
+    selection: Selection,
+    deselected_files: usize,
+    current_root: Option<PathBuf>,
+    /// Where records go as they are added, when streaming
+    stream: Option<Box<dyn Write>>,
+    retain_files: bool,
+    output_closed: bool,
 }
 
 impl Statistics {
@@ -15,18 +28,73 @@ impl Statistics {
             total_files: 0,
             encoding_counts: HashMap::new(),
             files: Vec::new(),
+            filename_issues: Vec::new(),
+            errors: Vec::new(),
+            selection: Selection::default(),
+            deselected_files: 0,
+            current_root: None,
+            stream: None,
+            retain_files: true,
+            output_closed: false,
         }
     }
 
-    pub fn add_file(&mut self, path: PathBuf, name: String, encoding: FileEncoding) {
+    /// Only files matching `selection` are counted, reported and converted.
+    pub fn with_selection(selection: Selection) -> Self {
+        Statistics { selection, ..Self::new() }
+    }
+
+    /// Root that files added from now on were found under, or None for
+    /// files named individually.
+    pub fn set_root(&mut self, root: Option<PathBuf>) {
+        self.current_root = root;
+    }
+
+    /// Writes each file, error and file name issue as an `ndjson` record as
+    /// soon as it is added. Unless `retain_files` is set, file reports are
+    /// not kept afterwards, so memory use does not grow with the scan.
+    pub fn set_streaming(&mut self, retain_files: bool) {
+        self.stream_to(Box::new(io::stdout()), retain_files);
+    }
+
+    fn stream_to(&mut self, out: Box<dyn Write>, retain_files: bool) {
+        self.stream = Some(out);
+        self.retain_files = retain_files;
+    }
+
+    pub fn add_file(&mut self, mut report: FileReport) {
+        report.root = self.current_root.clone();
+        if !self.selection.matches(&report) {
+            self.deselected_files += 1;
+            return;
+        }
         self.total_files += 1;
-        *self.encoding_counts.entry(encoding.clone()).or_insert(0) += 1;
-        
-        self.files.push(FileReport {
-            path,
-            name,
-            encoding,
-        });
+        *self.encoding_counts.entry(report.encoding.clone()).or_insert(0) += 1;
+        if self.stream.is_some() {
+            self.emit(&StreamRecord::File(&relative_report(&report)));
+        }
+        if self.retain_files {
+            self.files.push(report);
+        }
+    }
+
+    pub fn add_filename_issue(&mut self, issue: FilenameIssue) {
+        if self.stream.is_some() {
+            self.emit(&StreamRecord::FilenameIssue(&issue));
+        }
+        self.filename_issues.push(issue);
+    }
+
+    pub fn add_error(&mut self, path: &Path, error: &io::Error) {
+        self.add_scan_error(path, FileError::from(error));
+    }
+
+    pub fn add_scan_error(&mut self, path: &Path, error: FileError) {
+        let error = ScanError { path: path.to_path_buf(), error };
+        if self.stream.is_some() {
+            self.emit(&StreamRecord::Error(&error));
+        }
+        self.errors.push(error);
     }
 
     pub fn generate_report(&self) -> ScanReport {
@@ -46,8 +114,11 @@ impl Statistics {
 
         ScanReport {
             total_files: self.total_files,
-            files: self.files.clone(),
+            deselected_files: self.deselected_files,
+            files: self.files.iter().map(relative_report).collect(),
             encoding_stats: stats,
+            filename_issues: self.filename_issues.clone(),
+            errors: self.errors.clone(),
         }
     }
 
@@ -56,9 +127,114 @@ impl Statistics {
         write_output(&report, format)
     }
 
-    pub fn get_files(&self) -> Vec<(PathBuf, String, FileEncoding)> {
-        self.files.iter()
-            .map(|f| (f.path.clone(), f.name.clone(), f.encoding.clone()))
-            .collect()
+    /// Whether the reader of the streamed records went away, in which case
+    /// there is no point in scanning any further.
+    pub fn output_closed(&self) -> bool {
+        self.output_closed
+    }
+
+    fn emit(&mut self, record: &StreamRecord) {
+        let Some(out) = self.stream.as_mut() else { return };
+        if self.output_closed {
+            return;
+        }
+        match write_record(out, record) {
+            Err(e) if e.kind() == io::ErrorKind::BrokenPipe => self.output_closed = true,
+            Err(e) => eprintln!("Error writing record: {}", e),
+            Ok(()) => {}
+        }
+    }
+
+    pub fn get_files(&self) -> &[FileReport] {
+        &self.files
+    }
+
+    pub fn get_filename_issues(&self) -> &[FilenameIssue] {
+        &self.filename_issues
+    }
+}
+
+
+/// Copy of a report with its path made relative to its root, for output.
+fn relative_report(file: &FileReport) -> FileReport {
+    let mut file = file.clone();
+    if let Some(relative) = file.root.as_deref().and_then(|root| file.path.strip_prefix(root).ok()) {
+        file.path = relative.to_path_buf();
+    }
+    file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+    use serde_json::Value;
+    use crate::access::ErrorKind;
+    use crate::filenames::check_name;
+    use crate::output::summary_record;
+    use crate::selection::Selector;
+    use crate::testing::{analyze, TempDir};
+
+    /// Collects the streamed records where the test can read them back.
+    #[derive(Clone, Default)]
+    struct Records(Rc<RefCell<Vec<u8>>>);
+
+    impl Write for Records {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.borrow_mut().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl Records {
+        fn lines(&self) -> Vec<Value> {
+            String::from_utf8(self.0.borrow().clone()).unwrap()
+                .lines()
+                .map(|line| serde_json::from_str(line).unwrap())
+                .collect()
+        }
+    }
+
+    #[test]
+    fn streams_records_as_they_are_added() {
+        let dir = TempDir::new();
+        let root = dir.path().join("in");
+        let latin = analyze(&dir.write("in/latin.txt", b"caf\xe9\n"));
+        let ascii = analyze(&dir.write("in/plain.txt", "plain\n"));
+        let issue = check_name(&root.join("cafe\u{301}.txt"), true).unwrap();
+
+        let records = Records::default();
+        let mut stats = Statistics::with_selection(Selection::default().with(Selector::Bom(false)));
+        stats.stream_to(Box::new(records.clone()), false);
+        stats.set_root(Some(root.clone()));
+        stats.add_file(latin);
+        stats.add_scan_error(&root.join("locked"), FileError::new(ErrorKind::Locked, "busy".to_string()));
+        stats.add_filename_issue(issue);
+        stats.add_file(ascii);
+        // Deselected files are counted but not streamed
+        stats.add_file(analyze(&dir.write("in/bom.txt", "\u{feff}bom\n")));
+
+        let lines = records.lines();
+        let kinds: Vec<&str> = lines.iter().map(|r| r["type"].as_str().unwrap()).collect();
+        assert_eq!(kinds, ["file", "error", "filename-issue", "file"]);
+        assert_eq!(lines[0]["path"], "latin.txt");
+        assert_eq!(lines[0]["encoding"], "ISO-8859-1");
+        assert_eq!(lines[1]["kind"], "locked");
+        assert_eq!(lines[3]["path"], "plain.txt");
+        // Streamed reports are not kept unless asked for
+        assert!(stats.get_files().is_empty());
+
+        let report = stats.generate_report();
+        let summary = serde_json::to_value(summary_record(&report)).unwrap();
+        assert_eq!(summary["type"], "summary");
+        assert_eq!(summary["total_files"], 2);
+        assert_eq!(summary["deselected_files"], 1);
+        assert_eq!(summary["errors"], 1);
+        assert_eq!(summary["filename_issues"], 1);
+        assert_eq!(summary["encoding_stats"].as_array().unwrap().len(), 2);
     }
 }
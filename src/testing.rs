@@ -0,0 +1,93 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use crate::analysis::{analyze_file, AnalysisOptions};
+use crate::config::{Config, SourceOverrides};
+use crate::conversion::{ConversionOptions, LineEnding};
+use crate::output::FileReport;
+
+static NEXT_ID: AtomicUsize = AtomicUsize::new(0);
+
+/// Directory below the system temp directory that is removed with
+/// everything in it when dropped, read-only files included.
+pub struct TempDir {
+    path: PathBuf,
+}
+
+impl TempDir {
+    pub fn new() -> Self {
+        let path = std::env::temp_dir().join(format!(
+            "convert-rust-test-{}-{}",
+            std::process::id(),
+            NEXT_ID.fetch_add(1, Ordering::Relaxed)
+        ));
+        fs::create_dir_all(&path).unwrap();
+        TempDir { path }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Writes a file below the directory, creating its parents.
+    pub fn write(&self, name: impl AsRef<Path>, content: impl AsRef<[u8]>) -> PathBuf {
+        let path = self.path.join(name);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).unwrap();
+        }
+        fs::write(&path, content).unwrap();
+        path
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        make_writable(&self.path);
+        let _ = fs::remove_dir_all(&self.path);
+    }
+}
+
+fn make_writable(path: &Path) {
+    let Ok(metadata) = fs::symlink_metadata(path) else { return };
+    if metadata.file_type().is_symlink() {
+        return;
+    }
+    let _ = fs::set_permissions(path, crate::access::writable(metadata.permissions()));
+    if metadata.is_dir() {
+        for entry in fs::read_dir(path).into_iter().flatten().flatten() {
+            make_writable(&entry.path());
+        }
+    }
+}
+
+/// Analyzes a file on disk the way a scan with --security does.
+pub fn analyze(path: &Path) -> FileReport {
+    let overrides = SourceOverrides::new(&[], None, &Config::default()).unwrap();
+    let options = AnalysisOptions {
+        trust_declarations: false,
+        security_scan: true,
+        nfc_filenames: false,
+        scan_archives: false,
+        decompress: true,
+        overrides: &overrides,
+        expected_encodings: &[],
+        target_encoding: None,
+    };
+    let name = path.file_name().unwrap().to_string_lossy().into_owned();
+    analyze_file(path, name, &options)
+}
+
+/// Conversion options with every optional step turned off.
+pub fn conversion_options() -> ConversionOptions {
+    ConversionOptions {
+        line_ending: LineEnding::Keep,
+        repair_mixed: false,
+        repair_mojibake: 0,
+        mojibake_confidence: 0.6,
+        rewrite_declarations: false,
+        properties: false,
+        normalize: None,
+        strip_embedded_boms: false,
+        decompress: true,
+    }
+}